@@ -0,0 +1,753 @@
+use std::env;
+use std::io::Read;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use reqwest::blocking::Response;
+use reqwest::header::{HeaderValue, COOKIE, RETRY_AFTER};
+use reqwest::Proxy;
+use reqwest::StatusCode;
+
+use crate::submit::{format_duration, Cooldowns};
+use crate::{dry_run, logging, progress, provider, puzzle};
+
+/// Environment variable to use another server than adventofcode.com, such as
+/// a mirror or a mock server for testing
+pub const BASE_URL_ENV_VAR: &str = "AOC_BASE_URL";
+
+/// Base URL given with --base-url, which takes precedence over the others
+static BASE_URL_OVERRIDE: Mutex<Option<String>> = Mutex::new(None);
+/// Base URL from aochelper.toml
+static BASE_URL: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_base_url_override(url: Option<String>) {
+    *BASE_URL_OVERRIDE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = url;
+}
+
+pub fn set_base_url(url: Option<String>) {
+    *BASE_URL.lock().unwrap_or_else(|err| err.into_inner()) = url;
+}
+
+/// URL of the server to talk to: from --base-url, $AOC_BASE_URL or
+/// aochelper.toml, or else the provider's site
+pub fn base_url() -> String {
+    let url = BASE_URL_OVERRIDE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+        .or_else(|| {
+            env::var(BASE_URL_ENV_VAR)
+                .ok()
+                .filter(|url| !url.is_empty())
+        })
+        .or_else(|| {
+            BASE_URL
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone()
+        })
+        .unwrap_or_else(|| provider::active().default_url().to_string());
+    url.trim_end_matches('/').to_string()
+}
+
+/// Host name of the provider's site, such as adventofcode.com, for messages
+/// and the cooldowns file
+pub fn site() -> &'static str {
+    let url = provider::active().default_url();
+    let host = url.split_once("://").map_or(url, |(_, host)| host);
+    host.trim_end_matches('/')
+}
+
+/// How often to try a request again after a network error or a temporary
+/// server error, and how long to wait in between: `delay`, doubled for every
+/// retry, plus up to `jitter`
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+    pub jitter: Duration,
+}
+
+impl RetryPolicy {
+    pub const DEFAULT: RetryPolicy = RetryPolicy {
+        retries: 3,
+        delay: Duration::from_secs(1),
+        jitter: Duration::from_millis(500),
+    };
+
+    fn wait(&self, attempt: u32) -> Duration {
+        self.delay.saturating_mul(2u32.saturating_pow(attempt)) + jitter(self.jitter)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::DEFAULT
+    }
+}
+
+/// A random duration up to `max`, to keep clients from sending requests in
+/// lockstep
+pub fn jitter(max: Duration) -> Duration {
+    // Good enough randomness for that
+    let random = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    max.mul_f64(f64::from(random) / 1e9)
+}
+
+/// How long requests may take before they fail, and are tried again
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timeouts {
+    /// To connect to the server
+    pub connect: Option<Duration>,
+    /// For the whole request, from connecting to reading the response
+    pub request: Option<Duration>,
+}
+
+impl Timeouts {
+    const DEFAULT: Timeouts = Timeouts {
+        connect: Some(Duration::from_secs(10)),
+        request: Some(Duration::from_secs(30)),
+    };
+
+    /// These timeouts, with the ones in `other` for those not set
+    pub fn or(self, other: Timeouts) -> Timeouts {
+        Timeouts {
+            connect: self.connect.or(other.connect),
+            request: self.request.or(other.request),
+        }
+    }
+}
+
+/// Key in the cooldowns file for how long the site asked us to back off, with
+/// a 429 or 503 response: the site's host name
+fn throttle_key() -> String {
+    site().to_string()
+}
+
+/// Longest Retry-After to wait out before trying again, rather than failing
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Back-off after a 429 response without a Retry-After header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Error returned when adventofcode.com asked us to back off, before or now
+#[derive(Debug)]
+pub struct Throttled {
+    pub wait: Duration,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Throttled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} Try again in {}.",
+            self.reason,
+            format_duration(self.wait)
+        )
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// Fail without sending anything if adventofcode.com asked us to back off
+/// from all requests, or from this URL, and that time isn't over yet
+fn check_throttle(url: &str) -> Result<()> {
+    let cooldowns = match Cooldowns::load() {
+        Ok(cooldowns) => cooldowns,
+        Err(err) => {
+            tracing::warn!("Failed to read the cooldowns: {err:#}");
+            return Ok(());
+        }
+    };
+    if let Some(wait) = cooldowns.remaining(&throttle_key()) {
+        return Err(throttled(wait));
+    }
+    match cooldowns.remaining(url) {
+        Some(wait) => Err(locked(wait)),
+        None => Ok(()),
+    }
+}
+
+/// Remember to back off from `key` for `wait`, for later runs as well
+fn record_throttle(key: &str, wait: Duration) {
+    if let Err(err) = Cooldowns::record(key.to_string(), Some(wait)) {
+        tracing::warn!("Failed to record the cooldown: {err:#}");
+    }
+}
+
+/// How long a 429 or 503 response asks us to back off, from its Retry-After
+/// header, which is either a number of seconds or a date
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Handle a response asking us to back off: remember how long for, and
+/// return how long that is, if it is one
+fn throttle(res: &Response) -> Option<Duration> {
+    let wait = match res.status() {
+        StatusCode::TOO_MANY_REQUESTS => retry_after(res).unwrap_or(DEFAULT_RETRY_AFTER),
+        StatusCode::SERVICE_UNAVAILABLE => retry_after(res)?,
+        _ => return None,
+    };
+    record_throttle(&throttle_key(), wait);
+    Some(wait)
+}
+
+fn throttled(wait: Duration) -> anyhow::Error {
+    Throttled {
+        wait,
+        reason: format!("{} asked for fewer requests.", site()),
+    }
+    .into()
+}
+
+/// Error returned for a puzzle that isn't unlocked yet
+#[derive(Debug)]
+pub struct NotUnlocked(pub String);
+
+impl std::fmt::Display for NotUnlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotUnlocked {}
+
+fn locked(wait: Duration) -> anyhow::Error {
+    NotUnlocked(format!(
+        "This puzzle isn't unlocked yet. Try again in {}.",
+        format_duration(wait)
+    ))
+    .into()
+}
+
+/// Default shortest time between two requests to the same kind of page, see
+/// [`pace`]
+pub const DEFAULT_REQUEST_INTERVAL: Duration = Duration::from_secs(5);
+
+static REQUEST_INTERVAL: Mutex<Duration> = Mutex::new(DEFAULT_REQUEST_INTERVAL);
+
+/// Set the shortest time between two requests to the same kind of page
+pub fn set_request_interval(interval: Duration) {
+    *REQUEST_INTERVAL
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = interval;
+}
+
+static PARALLEL_REQUESTS: Mutex<u32> = Mutex::new(1);
+
+/// Set how many requests to the same kind of page may go out at once, for
+/// `--jobs`. Over time they still average one per request interval.
+pub fn set_parallel_requests(requests: usize) {
+    *PARALLEL_REQUESTS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = requests.clamp(1, u32::MAX as usize) as u32;
+}
+
+/// Wait until the request interval has passed since the last request to the
+/// same kind of page as `url`, in this run or an earlier one, so that running
+/// aochelper in a loop doesn't flood adventofcode.com. The time of the next
+/// allowed request is kept with the cooldowns. With parallel requests, that
+/// many may go out together before the interval applies.
+fn pace(url: &str) {
+    let interval = *REQUEST_INTERVAL
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let parallel = *PARALLEL_REQUESTS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let base_url = base_url();
+    let path = url.trim_start_matches(&base_url);
+    let endpoint = if path.ends_with("/input") {
+        "input"
+    } else if path.ends_with("/answer") {
+        "answer"
+    } else if path.contains("/day/") {
+        "puzzle"
+    } else {
+        "other"
+    };
+    let key = format!("pace:{endpoint}");
+    // The slot after the last one taken is reserved while holding the lock,
+    // and waited for after releasing it, so that requests running side by side
+    // each get their own slot without waiting for each other to be sent. Up
+    // to `parallel` slots may be reserved ahead, like tokens in a bucket.
+    let wait = Cooldowns::update(|cooldowns| {
+        let next = cooldowns.remaining(&key).unwrap_or_default();
+        cooldowns.set(key, Some(next + interval));
+        next.saturating_sub(interval * (parallel - 1))
+    });
+    match wait {
+        Ok(wait) if !wait.is_zero() => {
+            progress::note(format_args!(
+                "Waiting {:.1}s before the next request to {}...",
+                wait.as_secs_f64(),
+                site()
+            ));
+            thread::sleep(wait);
+        }
+        Ok(_) => (),
+        Err(err) => tracing::warn!("Failed to record the time of the request: {err:#}"),
+    }
+}
+
+/// What the client is built from: the command line options, and else the
+/// ones in aochelper.toml
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    pub timeouts: Timeouts,
+    /// Without one, reqwest goes through the proxy in $HTTPS_PROXY or
+    /// $HTTP_PROXY, unless the host is in $NO_PROXY
+    pub proxy: Option<String>,
+    /// Contact details, such as an email address, to add to the User-Agent
+    /// header, as Advent of Code asks of automated tools
+    pub user_agent_contact: Option<String>,
+    pub retry_policy: RetryPolicy,
+}
+
+/// Client for all requests of a run, so that connections to the server are
+/// kept alive from one request to the next. It is built in `run` and passed to
+/// whatever makes requests; the underlying reqwest client is only built on
+/// first use, so that a bad proxy setting doesn't stop commands that make
+/// none.
+#[derive(Debug)]
+pub struct Client {
+    options: ClientOptions,
+    http: OnceLock<reqwest::blocking::Client>,
+}
+
+impl Client {
+    pub fn new(options: ClientOptions) -> Client {
+        Client {
+            options,
+            http: OnceLock::new(),
+        }
+    }
+
+    /// The reqwest client, also for requests to other sites than the puzzle
+    /// site, which should go through the same proxy. Clones share the
+    /// connection pool.
+    pub fn http(&self) -> Result<reqwest::blocking::Client> {
+        if let Some(http) = self.http.get() {
+            return Ok(http.clone());
+        }
+        let http = self.build()?;
+        Ok(self.http.get_or_init(|| http).clone())
+    }
+
+    /// Client that accepts compressed responses, with the timeouts and proxy
+    /// of the options
+    fn build(&self) -> Result<reqwest::blocking::Client> {
+        let timeouts = self.options.timeouts.or(Timeouts::DEFAULT);
+        let mut builder = reqwest::blocking::Client::builder()
+            .user_agent(user_agent(self.options.user_agent_contact.as_deref()))
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.request);
+        if let Some(proxy) = &self.options.proxy {
+            // user:password@ in the URL logs into the proxy
+            if let Some(password) = reqwest::Url::parse(proxy)
+                .ok()
+                .and_then(|url| url.password().map(str::to_string))
+            {
+                logging::register_secret(&password);
+            }
+            tracing::debug!("Using proxy {proxy}");
+            builder = builder.proxy(
+                Proxy::all(proxy)
+                    .map_err(|err| anyhow::anyhow!("Invalid proxy {proxy:?}: {err}"))?,
+            );
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// `aochelper/<version> (+<repository>)`, with the contact details if set
+fn user_agent(contact: Option<&str>) -> String {
+    let name = env!("CARGO_PKG_NAME");
+    let version = env!("CARGO_PKG_VERSION");
+    let repository = env!("CARGO_PKG_REPOSITORY");
+    match contact {
+        Some(contact) => format!("{name}/{version} (+{repository}; {contact})"),
+        None => format!("{name}/{version} (+{repository})"),
+    }
+}
+
+/// GET a page, trying again after network errors and temporary server errors.
+/// Only for requests that are safe to repeat.
+#[tracing::instrument(name = "request", skip_all, fields(method = "GET", url = url))]
+fn get(client: &Client, url: &str, cookie: &str) -> Result<Response> {
+    if dry_run::enabled() {
+        dry_run::would(format_args!("GET {url}"));
+        return Err(dry_run::Stopped.into());
+    }
+    check_throttle(url)?;
+    pace(url);
+    let http = client.http()?;
+    let policy = client.options.retry_policy;
+    let mut attempt = 0;
+    loop {
+        let result = http.get(url).header(COOKIE, session_cookie(cookie)?).send();
+        let mut wait = policy.wait(attempt);
+        let problem = match result {
+            Ok(res) => {
+                tracing::debug!("Response: {}", res.status());
+                match throttle(&res) {
+                    Some(after) if attempt < policy.retries && after <= MAX_RETRY_AFTER => {
+                        wait = after;
+                        res.status().to_string()
+                    }
+                    Some(after) => return Err(throttled(after)),
+                    None if attempt >= policy.retries || !is_temporary(res.status()) => {
+                        return Ok(res)
+                    }
+                    None => res.status().to_string(),
+                }
+            }
+            Err(err) if attempt < policy.retries && err.is_timeout() => "timed out".to_string(),
+            Err(err) if attempt < policy.retries && err.is_connect() => {
+                "could not connect".to_string()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        progress::note(format_args!(
+            "Request to {url} failed ({problem}), trying again in {:.1}s...",
+            wait.as_secs_f64()
+        ));
+        thread::sleep(wait);
+        attempt += 1;
+    }
+}
+
+/// Server errors worth trying again. 500 is left out, as that is what
+/// adventofcode.com answers to an invalid session key.
+fn is_temporary(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Error returned when adventofcode.com doesn't accept the session key
+#[derive(Debug)]
+pub struct InvalidSession;
+
+impl std::fmt::Display for InvalidSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid session key supplied. You may need to log into {} with your browser again.",
+            site()
+        )
+    }
+}
+
+impl std::error::Error for InvalidSession {}
+
+/// Download the input of a puzzle, exactly as adventofcode.com sent it
+pub fn get_puzzle_input(client: &Client, puzzle_url: String, cookie: &str) -> Result<Vec<u8>> {
+    tracing::debug!("Querying puzzle input from {puzzle_url}");
+    let input = fetch(client, &puzzle_url, cookie, "Puzzle input")?;
+    check_input(&input)?;
+    Ok(input)
+}
+
+/// Make sure a downloaded input isn't a web page or an error message, which
+/// adventofcode.com sometimes sends with a successful status
+fn check_input(input: &[u8]) -> Result<()> {
+    let contains = |text: &[u8]| input.windows(text.len()).any(|window| window == text);
+    if contains(b"Puzzle inputs differ by user") {
+        return Err(InvalidSession.into());
+    }
+    let start: Vec<u8> = input
+        .iter()
+        .skip_while(|byte| byte.is_ascii_whitespace())
+        .take(b"<!doctype".len())
+        .map(u8::to_ascii_lowercase)
+        .collect();
+    if start.starts_with(b"<!doctype") || start.starts_with(b"<html") {
+        let message = format!("{} sent a web page instead of the puzzle input.", site());
+        return Err(match article_text(&String::from_utf8_lossy(input)) {
+            Some(text) => anyhow::anyhow!("{message} {text}"),
+            None => anyhow::anyhow!(message),
+        });
+    }
+    Ok(())
+}
+
+/// Download the description of a puzzle: the `<article>` elements of the
+/// puzzle page, one for each part that is unlocked
+pub fn get_puzzle_page(client: &Client, page_url: String, cookie: &str) -> Result<String> {
+    tracing::debug!("Querying puzzle description from {page_url}");
+    let body = String::from_utf8(fetch(client, &page_url, cookie, "Puzzle")?)
+        .with_context(|| format!("Puzzle page {page_url} is not valid UTF-8"))?;
+    let articles = articles(&body);
+    if articles.is_empty() {
+        return Err(anyhow::anyhow!("No puzzle description found on {page_url}"));
+    }
+    Ok(articles.join("\n"))
+}
+
+/// Body of a successful response, as it was sent. Error responses are only
+/// read as text to tell what went wrong.
+fn fetch(client: &Client, url: &str, cookie: &str, what: &str) -> Result<Vec<u8>> {
+    let res = get(client, url, cookie)?;
+    let status = res.status();
+    let body = Vec::from(res.bytes()?);
+    if status == reqwest::StatusCode::OK {
+        return Ok(body);
+    }
+
+    let body = String::from_utf8_lossy(&body);
+    match status {
+        reqwest::StatusCode::NOT_FOUND if body.contains("Please don't repeatedly request") => {
+            Err(not_unlocked(url))
+        }
+        reqwest::StatusCode::NOT_FOUND => Err(anyhow::anyhow!("{what} for {url} not found.")),
+        reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
+            Err(InvalidSession.into())
+        }
+        _ => Err(anyhow::anyhow!(
+            "Error getting {}: {status}\n{body}",
+            what.to_lowercase()
+        )),
+    }
+}
+
+/// Error for a puzzle that isn't unlocked yet. adventofcode.com asks not to
+/// request it again until it is, so remember when that will be, from the year
+/// and day in the URL.
+fn not_unlocked(url: &str) -> anyhow::Error {
+    let base_url = base_url();
+    let mut parts = url.trim_start_matches(&base_url).split('/').skip(1);
+    let year = parts.next().and_then(|year| year.parse::<u16>().ok());
+    let day = parts.nth(1).and_then(|day| day.parse::<u8>().ok());
+    let unlock = year
+        .zip(day)
+        .and_then(|(year, day)| unlock_time(year, day))
+        .and_then(|unlock| (unlock - Utc::now()).to_std().ok());
+    match unlock {
+        Some(wait) => {
+            record_throttle(url, wait);
+            locked(wait)
+        }
+        None => NotUnlocked("This puzzle isn't unlocked yet.".to_string()).into(),
+    }
+}
+
+/// The `<article>` elements of a page, tags included
+fn articles(body: &str) -> Vec<&str> {
+    let mut articles = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("<article") {
+        let Some(len) = rest[start..].find("</article>") else {
+            break;
+        };
+        let end = start + len + "</article>".len();
+        articles.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    articles
+}
+
+/// Build the session cookie header, marked sensitive so that it is left out of
+/// reqwest's debug output
+fn session_cookie(cookie: &str) -> Result<HeaderValue> {
+    logging::register_secret(cookie);
+    let name = provider::active().cookie_name();
+    let mut value = HeaderValue::from_str(&format!("{name}={cookie}"))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// When a puzzle unlocks, see [`Provider::unlock_time`](provider::Provider::unlock_time)
+pub fn unlock_time(year: u16, day: u8) -> Option<DateTime<Utc>> {
+    provider::active().unlock_time(year, day)
+}
+
+pub fn is_unlocked(year: u16, day: u8) -> bool {
+    unlock_time(year, day).is_some_and(|unlock| unlock <= Utc::now())
+}
+
+/// Today's puzzle, see [`Provider::current_puzzle`](provider::Provider::current_puzzle)
+pub fn current_puzzle() -> Option<(u16, u8)> {
+    provider::active().current_puzzle()
+}
+
+/// Year, day and unlock time of the next puzzle to unlock. Outside of the
+/// event, that is the first puzzle of the next one.
+pub fn next_unlock() -> Option<(u16, u8, DateTime<Utc>)> {
+    let year = max_year();
+    let now = Utc::now();
+    [year, year + 1]
+        .into_iter()
+        .flat_map(|year| (1..=25).map(move |day| (year, day)))
+        .find_map(|(year, day)| {
+            unlock_time(year, day)
+                .filter(|unlock| *unlock > now)
+                .map(|unlock| (year, day, unlock))
+        })
+}
+
+pub fn max_year() -> u16 {
+    provider::active().max_year()
+}
+
+pub fn check_puzzle(year: u16, day: u8) -> Result<()> {
+    provider::active().check_puzzle(year, day)
+}
+
+/// Fail without sending anything if a puzzle isn't unlocked yet, rather than
+/// asking for it and getting a 404
+pub fn check_unlocked(year: u16, day: u8) -> Result<()> {
+    let Some(unlock) = unlock_time(year, day) else {
+        return Ok(());
+    };
+    let Ok(wait) = (unlock - Utc::now()).to_std() else {
+        return Ok(());
+    };
+    let message = if wait < Duration::from_secs(24 * 60 * 60) {
+        format!("Day {day} of {year} unlocks in {}.", format_duration(wait))
+    } else {
+        format!(
+            "Day {day} of {year} unlocks on {}.",
+            unlock.with_timezone(&Local).format("%Y-%m-%d at %H:%M")
+        )
+    };
+    Err(NotUnlocked(message).into())
+}
+
+pub fn build_puzzle_url(year: u16, day: u8) -> Result<String> {
+    check_puzzle(year, day)?;
+    check_unlocked(year, day)?;
+    Ok(base_url() + &provider::active().input_path(year, day))
+}
+
+pub fn build_page_url(year: u16, day: u8) -> Result<String> {
+    check_puzzle(year, day)?;
+    check_unlocked(year, day)?;
+    Ok(base_url() + &provider::active().page_path(year, day))
+}
+
+pub fn build_answer_url(year: u16, day: u8) -> Result<String> {
+    check_puzzle(year, day)?;
+    check_unlocked(year, day)?;
+    Ok(base_url() + &provider::active().answer_path(year, day))
+}
+
+pub fn build_leaderboard_url(year: u16) -> String {
+    base_url() + &provider::active().leaderboard_path(year)
+}
+
+pub fn build_stats_url(year: u16) -> String {
+    base_url() + &provider::active().stats_path(year)
+}
+
+/// Submit an answer for one part of a puzzle. Returns the message on the page
+/// Advent of Code responds with.
+#[tracing::instrument(name = "request", skip_all, fields(method = "POST", url = answer_url))]
+pub fn submit_answer(
+    client: &Client,
+    answer_url: String,
+    part: u8,
+    answer: &str,
+    cookie: &str,
+) -> Result<String> {
+    tracing::debug!("Submitting answer for part {part} to {answer_url}");
+    if dry_run::enabled() {
+        dry_run::would(format_args!(
+            "POST the answer to part {part} to {answer_url}"
+        ));
+        return Err(dry_run::Stopped.into());
+    }
+    check_throttle(&answer_url)?;
+    pace(&answer_url);
+    let mut res = client
+        .http()?
+        .post(&answer_url)
+        .header(COOKIE, session_cookie(cookie)?)
+        .form(&[("level", part.to_string().as_str()), ("answer", answer)])
+        .send()?;
+    tracing::debug!("Response: {}", res.status());
+    if let Some(wait) = throttle(&res) {
+        return Err(throttled(wait));
+    }
+    let mut body = String::new();
+    res.read_to_string(&mut body)?;
+
+    match res.status() {
+        reqwest::StatusCode::OK => article_text(&body)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected response to the answer:\n{body}")),
+        reqwest::StatusCode::NOT_FOUND => {
+            Err(anyhow::anyhow!("Puzzle for {} not found.", &answer_url))
+        }
+        reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
+            Err(InvalidSession.into())
+        }
+        _ => Err(anyhow::anyhow!(
+            "Error submitting answer: {}\n{body}",
+            res.status()
+        )),
+    }
+}
+
+/// Text of the `<article>` element of a page, with the tags stripped and the
+/// whitespace collapsed
+fn article_text(body: &str) -> Option<String> {
+    let (_, article) = body.split_once("<article")?;
+    let (_, article) = article.split_once('>')?;
+    let (article, _) = article.split_once("</article>")?;
+    let text = puzzle::unescape(&puzzle::strip_tags(article));
+    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Check a session key by requesting the Advent of Code front page, which
+/// shows the name of the logged in user. Returns the user name, or an error if
+/// the session key is not logged in.
+pub fn check_session(client: &Client, cookie: &str) -> Result<String> {
+    let base_url = base_url();
+    tracing::debug!("Checking session key against {base_url}");
+    let mut res = get(client, &base_url, cookie)?;
+    let mut body = String::new();
+    res.read_to_string(&mut body)?;
+    if !res.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Error checking session key: {}",
+            res.status()
+        ));
+    }
+
+    // Logged in users get a `<div class="user">name <span class="star-count">`
+    match body.split_once(r#"<div class="user">"#) {
+        Some((_, user)) => {
+            let name = user.split('<').next().unwrap_or_default().trim();
+            Ok(name.to_string())
+        }
+        None => Err(anyhow::anyhow!(
+            "Session key is not logged in. You may need to log into {} with your browser again.",
+            site()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn site_of_advent_of_code() {
+        // Also the key cooldowns were kept under before there were providers
+        assert_eq!(site(), "adventofcode.com");
+    }
+}
@@ -0,0 +1,527 @@
+//! Backing up what aochelper saved into a .tar.zst archive, and restoring it,
+//! e.g. on a new machine. Inside the archive, files are laid out by kind
+//! rather than by where they were: `inputs/`, `puzzles/`, `examples/`,
+//! `notes/`, `cache/<account>/` and `answers.json` for the answers in the
+//! history, so that they land where the new machine keeps them.
+//!
+//! Archives named .tar.gz or .tgz are compressed with gzip instead. Any of
+//! them, or a plain .tar, can be restored, whichever tool wrote it.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::history::{AnswerRecord, History};
+use crate::puzzle::{ExampleAnswers, EXAMPLE_ANSWERS_FILE};
+use crate::report::{self, say};
+use crate::{checksums, color, config, dry_run, vault};
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const ANSWERS_FILE: &str = "answers.json";
+const EXAMPLE_ANSWERS: &str = "examples/examples.toml";
+
+/// Where the files of each kind are on this machine
+pub struct Dirs<'a> {
+    pub inputs: &'a Path,
+    pub puzzles: &'a Path,
+    pub examples: &'a Path,
+    pub notes: &'a Path,
+}
+
+impl Dirs<'_> {
+    /// The directory files under `kind/` in the archive go to
+    fn for_kind(&self, kind: &str) -> Option<PathBuf> {
+        match kind {
+            "inputs" => Some(self.inputs.to_path_buf()),
+            "puzzles" => Some(self.puzzles.to_path_buf()),
+            "examples" => Some(self.examples.to_path_buf()),
+            "notes" => Some(self.notes.to_path_buf()),
+            "cache" => Some(config::cache_dir()?.join("inputs")),
+            _ => None,
+        }
+    }
+}
+
+/// A file in the archive
+struct Entry {
+    path: String,
+    data: Vec<u8>,
+}
+
+/// Year of a file named after a puzzle, such as `2022.05`, `2022.05.md` or
+/// `2022.05.1`
+fn file_year(name: &str) -> Option<u16> {
+    let (year, rest) = name.split_once('.')?;
+    let day = rest.split('.').next()?;
+    let is_digits = |text: &str, len| text.len() == len && text.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(year, 4) || !is_digits(day, 2) || name.ends_with(".part") {
+        return None;
+    }
+    year.parse().ok()
+}
+
+/// Whether a file belongs in an archive of `year`, or of every year
+fn wanted(name: &str, year: Option<u16>) -> bool {
+    file_year(name).is_some_and(|file_year| year.map_or(true, |year| year == file_year))
+}
+
+/// Only the expected answers to examples of `year`, or of every year
+fn of_year(mut answers: ExampleAnswers, year: Option<u16>) -> ExampleAnswers {
+    if let Some(year) = year {
+        answers.retain_year(year);
+    }
+    answers
+}
+
+/// Add the files in `dir` that belong in the archive under `prefix`
+fn add_dir(entries: &mut Vec<Entry>, dir: &Path, prefix: &str, year: Option<u16>) -> Result<()> {
+    let mut files = match fs::read_dir(dir) {
+        Ok(files) => files
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {:?}", dir)),
+    };
+    files.sort();
+    for path in files {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !wanted(name, year) || !path.is_file() {
+            continue;
+        }
+        let data = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        entries.push(Entry {
+            path: format!("{prefix}/{name}"),
+            data,
+        });
+    }
+    Ok(())
+}
+
+/// How an archive is compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    /// The compression of an archive named `tar_name`, going by its extension
+    fn of_name(tar_name: &Path) -> Result<Codec> {
+        match tar_name
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            None | Some("zst" | "tzst") => Ok(Codec::Zstd),
+            Some("gz" | "tgz") => Ok(Codec::Gzip),
+            Some(extension) => Err(anyhow::anyhow!(
+                "Archives are compressed with zstd or gzip, not .{extension}; \
+                 name it e.g. aoc-backup.tar.zst or aoc-backup.tar.gz."
+            )),
+        }
+    }
+}
+
+/// The entries as a tar archive
+fn tar(entries: &[Entry], writer: impl Write) -> Result<()> {
+    let mtime = chrono::Utc::now().timestamp().max(0) as u64;
+    let mut tar = tar::Builder::new(writer);
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(entry.data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, &entry.path, &entry.data[..])
+            .with_context(|| format!("Failed to archive {}", entry.path))?;
+    }
+    tar.finish()?;
+    Ok(())
+}
+
+fn write_archive(file: &Path, entries: &[Entry], codec: Codec, encrypt: bool) -> Result<()> {
+    let mut data = match codec {
+        Codec::Gzip => {
+            let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+            tar(entries, &mut gzip)?;
+            gzip.finish()?
+        }
+        Codec::Zstd => {
+            let mut zstd = zstd::Encoder::new(Vec::new(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            tar(entries, &mut zstd)?;
+            zstd.finish()?
+        }
+    };
+    if encrypt {
+        data = vault::encrypt_archive(&data)?;
+    }
+    if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(file, data).with_context(|| format!("Failed to write {:?}", file))
+}
+
+/// The regular files in a tar archive
+fn untar(reader: impl Read) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().replace('\\', "/");
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        entries.push(Entry { path, data });
+    }
+    Ok(entries)
+}
+
+/// The regular files in a .tar.zst, .tar.gz or plain .tar archive, which may
+/// be encrypted
+fn read_archive(file: &Path) -> Result<Vec<Entry>> {
+    let mut compressed = fs::read(file).with_context(|| format!("Failed to read {:?}", file))?;
+    if vault::is_encrypted_archive(&compressed) {
+        compressed = vault::decrypt_archive(&compressed)?;
+    }
+    let entries = if compressed.starts_with(&ZSTD_MAGIC) {
+        untar(zstd::Decoder::new(&compressed[..])?)
+    } else if compressed.starts_with(&GZIP_MAGIC) {
+        untar(GzDecoder::new(&compressed[..]))
+    } else if compressed.get(257..262) == Some(b"ustar") {
+        untar(&compressed[..])
+    } else {
+        return Err(anyhow::anyhow!(
+            "{} is not a .tar.zst, .tar.gz or .tar archive",
+            file.display()
+        ));
+    };
+    entries.with_context(|| format!("{} is not a valid archive", file.display()))
+}
+
+fn count_files(count: usize) -> String {
+    match count {
+        1 => "1 file".to_string(),
+        n => format!("{n} files"),
+    }
+}
+
+/// What to back up
+pub struct Archive<'a> {
+    pub file: &'a Path,
+    /// Only back up the files of this year
+    pub year: Option<u16>,
+    /// Encrypt the archive with a passphrase
+    pub encrypt: bool,
+    pub dirs: Dirs<'a>,
+}
+
+/// Write the inputs, puzzle descriptions, examples, notes, cached inputs and
+/// answers to an archive
+pub fn archive_cmd(archive: Archive) -> Result<()> {
+    // An encrypted archive may be named e.g. aoc-backup.tar.gz.enc
+    let tar_name = match archive.file.extension() {
+        Some(extension) if archive.encrypt && extension == "enc" => {
+            Path::new(archive.file.file_stem().unwrap_or_default())
+        }
+        _ => archive.file,
+    };
+    let codec = Codec::of_name(tar_name)?;
+    let Archive {
+        file,
+        year,
+        encrypt,
+        dirs,
+    } = archive;
+    let mut entries = Vec::new();
+    add_dir(&mut entries, dirs.inputs, "inputs", year)?;
+    add_dir(&mut entries, dirs.puzzles, "puzzles", year)?;
+    add_dir(&mut entries, dirs.examples, "examples", year)?;
+    let answers_path = dirs.examples.join(EXAMPLE_ANSWERS_FILE);
+    let answers = of_year(ExampleAnswers::load(&answers_path)?, year);
+    if !answers.is_empty() {
+        entries.push(Entry {
+            path: EXAMPLE_ANSWERS.to_string(),
+            data: toml::to_string(&answers)?.into_bytes(),
+        });
+    }
+    add_dir(&mut entries, dirs.notes, "notes", year)?;
+    if let Some(cache) = config::cache_dir().map(|dir| dir.join("inputs")) {
+        if let Ok(accounts) = fs::read_dir(&cache) {
+            let mut accounts = accounts
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect::<Vec<_>>();
+            accounts.sort();
+            for account in accounts {
+                add_dir(
+                    &mut entries,
+                    &cache.join(&account),
+                    &format!("cache/{account}"),
+                    year,
+                )?;
+            }
+        }
+    }
+    let answers = History::open()?.answers(year)?;
+    if !answers.is_empty() {
+        entries.push(Entry {
+            path: ANSWERS_FILE.to_string(),
+            data: serde_json::to_vec_pretty(&answers)?,
+        });
+    }
+
+    if entries.is_empty() {
+        say!("Nothing to archive.");
+        return Ok(());
+    }
+    let files: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+    report::set("archive", file);
+    report::set("files", &files);
+    report::set("encrypted", encrypt);
+    if dry_run::enabled() {
+        for path in &files {
+            dry_run::would(format_args!("archive {path}"));
+        }
+        let encrypted = if encrypt { "encrypted " } else { "" };
+        dry_run::would(format_args!("write {encrypted}{}", file.display()));
+        return Ok(());
+    }
+    tracing::info_span!("write", path = %file.display())
+        .in_scope(|| write_archive(file, &entries, codec, encrypt))?;
+    let count = count_files(entries.len());
+    say!(
+        "{}",
+        color::say(
+            &format!("Archived {count} to {}.", file.display()),
+            color::SUCCESS
+        )
+    );
+    Ok(())
+}
+
+/// What to restore
+pub struct Restore<'a> {
+    pub file: &'a Path,
+    /// Only restore the files of this year
+    pub year: Option<u16>,
+    /// Overwrite files that exist already
+    pub force: bool,
+    pub dirs: Dirs<'a>,
+}
+
+/// Where a file in the archive goes, if it is one aochelper writes. Paths
+/// that could lead out of the directories, such as with `..`, are refused.
+fn destination(dirs: &Dirs, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    let mut components = path.components();
+    let kind = components.next()?.as_os_str().to_str()?;
+    let rest = components.as_path();
+    let depth = if kind == "cache" { 2 } else { 1 };
+    if rest.components().count() != depth {
+        return None;
+    }
+    Some(dirs.for_kind(kind)?.join(rest))
+}
+
+/// Unpack an archive into this machine's directories and history
+pub fn restore_cmd(restore: Restore) -> Result<()> {
+    let Restore {
+        file,
+        year,
+        force,
+        dirs,
+    } = restore;
+    let entries = read_archive(file)?;
+    let (mut restored, mut kept) = (Vec::new(), Vec::new());
+    let mut answers_added = 0;
+    for entry in entries {
+        if entry.path == ANSWERS_FILE {
+            let mut answers: Vec<AnswerRecord> = serde_json::from_slice(&entry.data)
+                .with_context(|| format!("Invalid {ANSWERS_FILE} in {}", file.display()))?;
+            answers.retain(|answer| year.map_or(true, |year| year == answer.year));
+            if dry_run::enabled() {
+                dry_run::would(format_args!(
+                    "add the answers that are missing from the history, of {}",
+                    answers.len()
+                ));
+            } else {
+                answers_added = History::open()?.import_answers(&answers)?;
+            }
+            continue;
+        }
+        if entry.path == EXAMPLE_ANSWERS {
+            // Merged with the answers there are, which may be of other years
+            let answers = std::str::from_utf8(&entry.data)
+                .map_err(anyhow::Error::from)
+                .and_then(|toml| Ok(toml::from_str(toml)?))
+                .with_context(|| format!("Invalid {EXAMPLE_ANSWERS} in {}", file.display()))?;
+            let answers = of_year(answers, year);
+            let path = dirs.examples.join(EXAMPLE_ANSWERS_FILE);
+            if dry_run::enabled() {
+                dry_run::would(format_args!(
+                    "add the missing example answers to {}",
+                    path.display()
+                ));
+                continue;
+            }
+            let mut existing = ExampleAnswers::load(&path)?;
+            if existing.merge(answers, force) > 0 {
+                fs::create_dir_all(dirs.examples)?;
+                existing.save(&path)?;
+                restored.push(path);
+            }
+            continue;
+        }
+        let name = entry.path.rsplit('/').next().unwrap_or_default();
+        if !wanted(name, year) {
+            continue;
+        }
+        let Some(path) = destination(&dirs, &entry.path) else {
+            eprintln!(
+                "{} skipping {}, which aochelper doesn't know where to put.",
+                color::err("Warning:", color::WARNING),
+                entry.path
+            );
+            continue;
+        };
+        if path.exists() && !force {
+            kept.push(path);
+            continue;
+        }
+        if dry_run::enabled() {
+            dry_run::would(format_args!("restore {}", path.display()));
+        } else {
+            tracing::info_span!("write", path = %path.display()).in_scope(|| -> Result<()> {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&path, &entry.data)?;
+                if entry.path.starts_with("inputs/") {
+                    checksums::record(&path)?;
+                }
+                Ok(())
+            })?;
+        }
+        restored.push(path);
+    }
+
+    if !dry_run::enabled() {
+        let count = count_files(restored.len());
+        say!(
+            "{}",
+            color::say(
+                &format!("Restored {count} from {}.", file.display()),
+                color::SUCCESS
+            )
+        );
+        if answers_added > 0 {
+            let answers = match answers_added {
+                1 => "1 answer".to_string(),
+                n => format!("{n} answers"),
+            };
+            say!("Added {answers} to the history.");
+        }
+    }
+    if !kept.is_empty() {
+        say!(
+            "Kept {} that already exist. Pass --force to overwrite them.",
+            count_files(kept.len())
+        );
+    }
+    report::set("restored", &restored);
+    report::set("kept", &kept);
+    report::set("answers_added", answers_added);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codec_of_name() {
+        let codec = |name: &str| Codec::of_name(Path::new(name)).ok();
+        assert_eq!(codec("aoc-backup.tar.zst"), Some(Codec::Zstd));
+        assert_eq!(codec("aoc-backup"), Some(Codec::Zstd));
+        assert_eq!(codec("aoc-backup.tar.gz"), Some(Codec::Gzip));
+        assert_eq!(codec("aoc-backup.tgz"), Some(Codec::Gzip));
+        assert_eq!(codec("aoc-backup.zip"), None);
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        for (name, codec) in [
+            ("backup.tar.gz", Codec::Gzip),
+            ("backup.tar.zst", Codec::Zstd),
+        ] {
+            round_trip(name, codec);
+        }
+    }
+
+    fn round_trip(name: &str, codec: Codec) {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(name);
+        let long_path = format!("cache/{}/2022.03", "account".repeat(30));
+        let entries = [
+            ("inputs/2022.01", b"1000\n2000\n".to_vec()),
+            ("notes/2022.01.md", Vec::new()),
+            ("puzzles/2022.02.md", vec![b'x'; 100_000]),
+            (&long_path, b"long".to_vec()),
+        ]
+        .map(|(path, data)| Entry {
+            path: path.to_string(),
+            data,
+        });
+        write_archive(&file, &entries, codec, false).unwrap();
+        let read = read_archive(&file).unwrap();
+        assert_eq!(read.len(), entries.len());
+        for (read, written) in read.iter().zip(&entries) {
+            assert_eq!(read.path, written.path);
+            assert_eq!(read.data, written.data);
+        }
+        if codec == Codec::Zstd {
+            assert!(fs::metadata(&file).unwrap().len() < 10_000);
+        }
+    }
+
+    #[test]
+    fn plain_tar() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("backup.tar");
+        let entries = [Entry {
+            path: "inputs/2022.01".to_string(),
+            data: b"1\n".to_vec(),
+        }];
+        let mut tar_data = Vec::new();
+        tar(&entries, &mut tar_data).unwrap();
+        fs::write(&file, tar_data).unwrap();
+        let read = read_archive(&file).unwrap();
+        assert_eq!(read[0].path, "inputs/2022.01");
+        assert_eq!(read[0].data, b"1\n");
+    }
+
+    #[test]
+    fn not_an_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("backup.tar.zst");
+        fs::write(&file, "not an archive").unwrap();
+        let err = read_archive(&file).err().unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("is not a .tar.zst, .tar.gz or .tar archive"));
+    }
+}
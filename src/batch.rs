@@ -0,0 +1,112 @@
+//! Progress of batch downloads, so that an interrupted run can be resumed
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::data_dir;
+
+const MANIFEST_FILE: &str = "batches.toml";
+
+/// Serializes updates of the manifest between concurrent downloads
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Days finished so far by each unfinished batch, by [`Manifest::key`]
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Manifest(BTreeMap<String, Vec<u8>>);
+
+impl Manifest {
+    fn path() -> Option<PathBuf> {
+        Some(data_dir()?.join(MANIFEST_FILE))
+    }
+
+    /// Identifies a batch by everything that decides what it downloads, so
+    /// that only running the same command again resumes it
+    pub fn key(
+        account: Option<&str>,
+        year: u16,
+        days: &[u8],
+        output: &std::path::Path,
+        force: bool,
+    ) -> String {
+        let days: Vec<String> = days.iter().map(u8::to_string).collect();
+        format!(
+            "{}/{year}/{} -> {}{}",
+            account.unwrap_or("default"),
+            days.join(","),
+            output.display(),
+            if force { " (force)" } else { "" }
+        )
+    }
+
+    fn load() -> Result<Manifest> {
+        match Manifest::path() {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {:?}", &path))?;
+                toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", &path))
+            }
+            _ => Ok(Manifest::default()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = Manifest::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {:?}", &path))
+    }
+
+    fn update(update: impl FnOnce(&mut Manifest)) -> Result<()> {
+        let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut manifest = Manifest::load()?;
+        update(&mut manifest);
+        manifest.save()
+    }
+
+    /// Days of the batch finished in earlier runs
+    pub fn done(key: &str) -> Result<Vec<u8>> {
+        Ok(Manifest::load()?.0.remove(key).unwrap_or_default())
+    }
+
+    /// Record that a day of the batch is finished
+    pub fn complete(key: &str, day: u8) -> Result<()> {
+        Manifest::update(|manifest| {
+            let done = manifest.0.entry(key.to_string()).or_default();
+            if !done.contains(&day) {
+                done.push(day);
+                done.sort_unstable();
+            }
+        })
+    }
+
+    /// Forget that a day was finished by the batches of an account and year,
+    /// after its input was removed
+    pub fn forget_day(account: Option<&str>, year: u16, day: u8) -> Result<()> {
+        let prefix = format!("{}/{year}/", account.unwrap_or("default"));
+        Manifest::update(|manifest| {
+            for (_, done) in manifest
+                .0
+                .iter_mut()
+                .filter(|(key, _)| key.starts_with(&prefix))
+            {
+                done.retain(|done| *done != day);
+            }
+        })
+    }
+
+    /// Forget a batch once all of its days are finished
+    pub fn finish(key: &str) -> Result<()> {
+        Manifest::update(|manifest| {
+            manifest.0.remove(key);
+        })
+    }
+}
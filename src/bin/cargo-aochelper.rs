@@ -0,0 +1,67 @@
+//! `cargo aochelper`: runs aochelper as a cargo subcommand, for Rust
+//! projects. Cargo runs `cargo-aochelper aochelper <args>`; this passes
+//! `<args>` on to the aochelper installed next to it.
+//!
+//! Without an aochelper.toml in the current directory, the one at the root of
+//! the cargo workspace is used, so that every crate of a workspace with a
+//! crate per day shares it.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+const CONFIG_FILE: &str = "aochelper.toml";
+const CONFIG_ENV_VAR: &str = "AOCHELPER_CONFIG";
+
+/// aochelper, from the directory this was installed to, or else from PATH
+fn aochelper() -> PathBuf {
+    let name = format!("aochelper{}", env::consts::EXE_SUFFIX);
+    env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(&name))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Directory of the workspace the current directory is in, as cargo sees it
+fn workspace_root() -> Option<PathBuf> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let manifest = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+    manifest.parent().map(Path::to_path_buf)
+}
+
+fn main() {
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "aochelper") {
+        args.remove(0);
+    }
+
+    let mut command = Command::new(aochelper());
+    command.args(&args);
+    let config_given = env::var_os(CONFIG_ENV_VAR).is_some_and(|path| !path.is_empty())
+        || args.iter().any(|arg| {
+            arg == "--config" || arg.to_str().is_some_and(|arg| arg.starts_with("--config="))
+        });
+    if !config_given && !Path::new(CONFIG_FILE).exists() {
+        let current_dir = env::current_dir().ok();
+        if let Some(root) = workspace_root().filter(|root| Some(root) != current_dir.as_ref()) {
+            command.env(CONFIG_ENV_VAR, root.join(CONFIG_FILE));
+        }
+    }
+
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(err) => {
+            eprintln!("Error: Failed to run aochelper: {err}");
+            process::exit(1);
+        }
+    }
+}
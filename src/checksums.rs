@@ -0,0 +1,133 @@
+//! SHA-256 checksums of the downloaded inputs, kept in checksums.toml next to
+//! them, so that `aochelper verify` can tell when one was changed since, e.g.
+//! by an editor saving over it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const CHECKSUMS_FILE: &str = "checksums.toml";
+
+/// Checksum of each input in a directory, by file name
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Checksums(BTreeMap<String, String>);
+
+/// How an input compares to its checksum
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Modified,
+    Missing,
+    /// There is no checksum of it, such as for inputs downloaded before
+    /// checksums were kept
+    Unrecorded,
+}
+
+impl Status {
+    /// Name of the status, for the JSON output
+    pub fn id(&self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Modified => "modified",
+            Status::Missing => "missing",
+            Status::Unrecorded => "unrecorded",
+        }
+    }
+}
+
+/// Year and day of an input file named `{year}.{day}`
+pub fn input_puzzle(name: &str) -> Option<(u16, u8)> {
+    let (year, day) = name.split_once('.')?;
+    let is_digits = |text: &str, len| text.len() == len && text.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(year, 4) || !is_digits(day, 2) {
+        return None;
+    }
+    Some((year.parse().ok()?, day.parse().ok()?))
+}
+
+pub fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name()?.to_str().map(str::to_string)
+}
+
+impl Checksums {
+    pub fn load(dir: &Path) -> Result<Checksums> {
+        let path = dir.join(CHECKSUMS_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Checksums::default()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read {:?}", path)),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(CHECKSUMS_FILE);
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    /// Names of the inputs with a checksum
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// How the input at `path`, in the directory of these checksums,
+    /// compares to its checksum
+    pub fn check(&self, path: &Path) -> Result<Status> {
+        let expected = file_name(path).and_then(|name| self.0.get(&name));
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Status::Missing),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        Ok(match expected {
+            None => Status::Unrecorded,
+            Some(expected) if *expected == sha256(&data) => Status::Ok,
+            Some(_) => Status::Modified,
+        })
+    }
+}
+
+/// Record the checksum of the input at `path` as it is now
+pub fn record(path: &Path) -> Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), file_name(path)) else {
+        return Ok(());
+    };
+    let data = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let mut checksums = Checksums::load(dir)?;
+    checksums.0.insert(name, sha256(&data));
+    checksums.save(dir)
+}
+
+/// Forget the checksums of inputs that were removed on purpose
+pub fn forget(paths: &[PathBuf]) -> Result<()> {
+    let mut by_dir: BTreeMap<&Path, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        if let (Some(dir), Some(name)) = (path.parent(), file_name(path)) {
+            by_dir.entry(dir).or_default().push(name);
+        }
+    }
+    for (dir, names) in by_dir {
+        let mut checksums = Checksums::load(dir)?;
+        let before = checksums.0.len();
+        checksums.0.retain(|name, _| !names.contains(name));
+        if checksums.0.len() != before {
+            checksums.save(dir)?;
+        }
+    }
+    Ok(())
+}
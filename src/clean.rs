@@ -0,0 +1,157 @@
+//! Removing what aochelper leaves behind: cached inputs, copies of browser
+//! cookie databases left in the temporary directory by a run that crashed,
+//! and downloaded inputs
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+use crate::report::{self, say};
+use crate::{checksums, color, config, cookies};
+
+/// Copies of cookie databases younger than this may still be in use by
+/// another run
+const TEMP_COPY_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// What to remove
+pub struct Clean<'a> {
+    pub cache: bool,
+    /// Directory of downloaded inputs to clean, if any
+    pub inputs: Option<&'a Path>,
+    /// Only remove inputs of this year
+    pub year: Option<u16>,
+    /// Only remove cached inputs of this account
+    pub account: Option<&'a str>,
+    pub dry_run: bool,
+}
+
+/// Year of an input file named `{year}.{day}`, or of one that was being
+/// downloaded, `{year}.{day}.part`
+fn input_year(name: &str) -> Option<u16> {
+    let name = name.strip_suffix(".part").unwrap_or(name);
+    let (year, day) = name.split_once('.')?;
+    let is_digits = |text: &str, len| text.len() == len && text.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(year, 4) || !is_digits(day, 2) {
+        return None;
+    }
+    year.parse().ok()
+}
+
+/// Input files in `dir`, of `year` if given
+fn input_files(dir: &Path, year: Option<u16>) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {:?}", dir)),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(file_year) = name.to_str().and_then(input_year) else {
+            continue;
+        };
+        if year.map_or(true, |year| year == file_year) && entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Cached inputs, of every account unless one is given
+fn cached_inputs(account: Option<&str>, year: Option<u16>) -> Result<Vec<PathBuf>> {
+    let Some(cache) = config::cache_dir() else {
+        return Ok(Vec::new());
+    };
+    let inputs = cache.join("inputs");
+    let accounts = match account {
+        Some(account) => vec![inputs.join(account)],
+        None => match fs::read_dir(&inputs) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<_>>()?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read {:?}", inputs)),
+        },
+    };
+    let mut files = Vec::new();
+    for dir in accounts.iter().filter(|dir| dir.is_dir()) {
+        files.extend(input_files(dir, year)?);
+    }
+    Ok(files)
+}
+
+/// Directories of cookie database copies that weren't removed, because the
+/// run that made them crashed or was killed
+fn stale_temp_copies() -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(std::env::temp_dir())? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !name.to_string_lossy().starts_with(cookies::TEMP_DIR_PREFIX) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if metadata.is_dir() && age.is_some_and(|age| age >= TEMP_COPY_MIN_AGE) {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
+/// Remove the cache, stale copies of cookie databases and inputs, or with
+/// `dry_run`, list what would be removed
+pub fn clean_cmd(clean: Clean) -> Result<()> {
+    let mut paths = Vec::new();
+    if clean.cache {
+        paths.extend(cached_inputs(clean.account, clean.year)?);
+        paths.extend(stale_temp_copies()?);
+    }
+    let inputs = match clean.inputs {
+        Some(dir) => input_files(dir, clean.year)?,
+        None => Vec::new(),
+    };
+    paths.extend(inputs.iter().cloned());
+    paths.sort();
+
+    for path in &paths {
+        if clean.dry_run {
+            say!("Would remove {}", path.display());
+            continue;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+        .with_context(|| format!("Failed to remove {:?}", path))?;
+        say!("Removed {}", path.display());
+    }
+    if !clean.dry_run {
+        checksums::forget(&inputs)?;
+    }
+
+    let count = match paths.len() {
+        1 => "1 file".to_string(),
+        n => format!("{n} files"),
+    };
+    if paths.is_empty() {
+        say!("Nothing to remove.");
+    } else if clean.dry_run {
+        say!("Would remove {count}. Run again without --dry-run to remove them.");
+    } else {
+        say!(
+            "{}",
+            color::say(&format!("Removed {count}."), color::SUCCESS)
+        );
+    }
+    report::set("removed", &paths);
+    report::set("dry_run", clean.dry_run);
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! The system clipboard, for `paste --clipboard` and `get --clipboard`, used
+//! through the tools each platform comes with: pbpaste and pbcopy on macOS and
+//! PowerShell on Windows. On Linux it takes wl-clipboard, xclip or xsel,
+//! whichever is installed.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+
+#[cfg(windows)]
+fn powershell(script: &str) -> Command {
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", script]);
+    command
+}
+
+#[cfg(windows)]
+fn paste_commands() -> Vec<Command> {
+    vec![powershell("Get-Clipboard -Raw")]
+}
+
+#[cfg(windows)]
+fn copy_commands() -> Vec<Command> {
+    vec![powershell(
+        "[Console]::InputEncoding = [Text.Encoding]::UTF8; \
+         Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+    )]
+}
+
+#[cfg(target_os = "macos")]
+fn paste_commands() -> Vec<Command> {
+    vec![Command::new("pbpaste")]
+}
+
+#[cfg(target_os = "macos")]
+fn copy_commands() -> Vec<Command> {
+    vec![Command::new("pbcopy")]
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn paste_commands() -> Vec<Command> {
+    let mut wl_paste = Command::new("wl-paste");
+    wl_paste.arg("--no-newline");
+    let mut xclip = Command::new("xclip");
+    xclip.args(["-selection", "clipboard", "-out"]);
+    let mut xsel = Command::new("xsel");
+    xsel.args(["--clipboard", "--output"]);
+    vec![wl_paste, xclip, xsel]
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn copy_commands() -> Vec<Command> {
+    let mut xclip = Command::new("xclip");
+    xclip.args(["-selection", "clipboard", "-in"]);
+    let mut xsel = Command::new("xsel");
+    xsel.args(["--clipboard", "--input"]);
+    vec![Command::new("wl-copy"), xclip, xsel]
+}
+
+fn not_installed(tried: &[String]) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Can't use the clipboard: none of {} is installed.",
+        tried.join(", ")
+    )
+}
+
+/// The text on the clipboard
+pub fn read() -> Result<String> {
+    let mut tried = Vec::new();
+    for mut command in paste_commands() {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let output = match command.stdin(Stdio::null()).stderr(Stdio::null()).output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                tried.push(program);
+                continue;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to run {program}")),
+        };
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "{program} failed to read the clipboard: {}",
+                output.status
+            ));
+        }
+        return String::from_utf8(output.stdout)
+            .map_err(|_| anyhow::anyhow!("The clipboard doesn't hold text."));
+    }
+    Err(not_installed(&tried))
+}
+
+/// Put `text` on the clipboard
+pub fn write(text: &str) -> Result<()> {
+    let mut tried = Vec::new();
+    for mut command in copy_commands() {
+        let program = command.get_program().to_string_lossy().into_owned();
+        // xclip and xsel stay behind to hold the selection, so their output
+        // must not be waited for
+        let mut child = match command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                tried.push(program);
+                continue;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to run {program}")),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .with_context(|| format!("Failed to write to {program}"))?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "{program} failed to write to the clipboard: {status}"
+            ));
+        }
+        return Ok(());
+    }
+    Err(not_installed(&tried))
+}
@@ -0,0 +1,62 @@
+//! Colors for terminal output. Text is only colored on a terminal, and not
+//! with --no-color or $NO_COLOR.
+
+use std::env;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::report;
+
+/// ANSI attributes of a kind of text
+#[derive(Clone, Copy)]
+pub struct Style(&'static str);
+
+pub const SUCCESS: Style = Style("1;32");
+pub const FAILURE: Style = Style("1;31");
+pub const WARNING: Style = Style("1;33");
+/// Headings of tables
+pub const HEADING: Style = Style("1");
+/// Cells of tables for what is done, and for what is missing
+pub const DONE: Style = Style("32");
+pub const MISSING: Style = Style("33");
+/// Things that need no attention, such as skipped days
+pub const QUIET: Style = Style("2");
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn colors off, for --no-color
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+fn enabled(terminal: bool) -> bool {
+    terminal && !DISABLED.load(Ordering::Relaxed) && env::var_os("NO_COLOR").is_none()
+}
+
+fn paint(text: &str, style: Style, terminal: bool) -> String {
+    if enabled(terminal) {
+        format!("\x1b[{}m{text}\x1b[0m", style.0)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Color text printed with [`report::say`]
+pub fn say(text: &str, style: Style) -> String {
+    let terminal = if report::json() {
+        std::io::stderr().is_terminal()
+    } else {
+        std::io::stdout().is_terminal()
+    };
+    paint(text, style, terminal)
+}
+
+/// Color text printed to stderr
+pub fn err(text: &str, style: Style) -> String {
+    paint(text, style, std::io::stderr().is_terminal())
+}
+
+/// Whether text on stderr is colored, for the log output
+pub fn on_stderr() -> bool {
+    enabled(std::io::stderr().is_terminal())
+}
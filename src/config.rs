@@ -0,0 +1,859 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::hooks::{Hook, Hooks};
+use crate::notify::{self, WebhookEvent};
+use crate::report::say;
+use crate::{aoc, color, cookies, dry_run, logging, permissions, provider, report, suggest, vault};
+
+pub const CONFIG_FILE: &str = "aochelper.toml";
+/// Environment variable naming another configuration file than
+/// aochelper.toml
+pub const CONFIG_ENV_VAR: &str = "AOCHELPER_CONFIG";
+const SECRETS_FILE: &str = "secrets.toml";
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Config {
+    pub year: Option<u16>,
+    /// Session key stored in the project file with `--local`. Normally the
+    /// session key lives in the user's secrets file instead.
+    pub session_key: Option<String>,
+    pub session_key_created: Option<DateTime<Utc>>,
+    pub output_path: Option<PathBuf>,
+    pub browser: Option<String>,
+    pub profile: Option<String>,
+    pub container: Option<String>,
+    pub no_browser_cookie: Option<bool>,
+    pub default_account: Option<String>,
+    /// Retry policy for requests, see [`aoc::RetryPolicy`]
+    pub retries: Option<u32>,
+    pub retry_delay: Option<f64>,
+    pub retry_jitter: Option<f64>,
+    /// Timeouts for requests in seconds, see [`aoc::Timeouts`]
+    pub connect_timeout: Option<f64>,
+    pub timeout: Option<f64>,
+    /// Puzzle site, see [`provider`]
+    pub provider: Option<String>,
+    /// HTTP(S) proxy to go through, instead of $HTTPS_PROXY or $HTTP_PROXY
+    pub proxy: Option<String>,
+    /// Server to talk to instead of adventofcode.com
+    pub base_url: Option<String>,
+    /// How many days `get` and `puzzle` download at a time
+    pub jobs: Option<usize>,
+    /// Shortest time between requests to the same kind of page, in seconds
+    pub request_interval: Option<f64>,
+    /// Contact details to add to the User-Agent header
+    pub user_agent_contact: Option<String>,
+    /// Regex picking the day out of the name of the current directory, see
+    /// [`Config::day_from_dir`]
+    pub day_pattern: Option<String>,
+    /// File to append logs to, besides the terminal
+    pub log_file: Option<PathBuf>,
+    /// Whether to look for a new release once a day, see [`crate::update`]
+    pub update_check: Option<bool>,
+    /// Whether to show desktop notifications, see [`crate::notify`]
+    pub notifications: Option<bool>,
+    /// URL to post events to, see [`crate::notify::webhook`]
+    pub webhook_url: Option<String>,
+    /// Events to post to the webhook, all of them if unset
+    pub webhook_events: Option<Vec<WebhookEvent>>,
+    /// Whether `submit` asks before submitting, on a terminal
+    pub confirm_submit: Option<bool>,
+    /// Whether downloading an input also saves the examples of the puzzle
+    pub save_examples: Option<bool>,
+    /// Where `edit` finds the solution of a day, see [`Config::solution_path`]
+    pub solution_path: Option<String>,
+    /// File `edit` starts new solutions from
+    pub solution_template: Option<PathBuf>,
+    /// Shell commands to run before and after some commands, see
+    /// [`crate::hooks`]
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Named Advent of Code accounts, each with its own session key and output
+    /// path. Tables have to come after plain values in TOML, so this is last.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub accounts: BTreeMap<String, Account>,
+    /// Contents of the user's secrets file, see [`secrets_path`]
+    #[serde(skip)]
+    pub secrets: Secrets,
+    /// Account selected with `--account` or `default_account`
+    #[serde(skip)]
+    active_account: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Account {
+    pub session_key: Option<String>,
+    pub session_key_created: Option<DateTime<Utc>>,
+    pub output_path: Option<PathBuf>,
+}
+
+/// Session keys kept out of the project directory, so that they don't end up
+/// committed along with aochelper.toml
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Secrets {
+    pub session_key: Option<String>,
+    pub session_key_created: Option<DateTime<Utc>>,
+    /// Whether session keys are encrypted with a passphrase, see `session lock`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
+    pub cached: Option<CachedKey>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub accounts: BTreeMap<String, AccountSecrets>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct AccountSecrets {
+    pub session_key: Option<String>,
+    pub session_key_created: Option<DateTime<Utc>>,
+    pub cached: Option<CachedKey>,
+}
+
+impl Secrets {
+    /// All session keys, of every account and cached ones included
+    pub fn keys_mut(&mut self) -> Vec<&mut String> {
+        let mut keys = Vec::new();
+        keys.extend(self.session_key.as_mut());
+        keys.extend(self.cached.as_mut().map(|cached| &mut cached.session_key));
+        for account in self.accounts.values_mut() {
+            keys.extend(account.session_key.as_mut());
+            keys.extend(
+                account
+                    .cached
+                    .as_mut()
+                    .map(|cached| &mut cached.session_key),
+            );
+        }
+        keys
+    }
+}
+
+/// A session key read from a browser, kept so that the browser's cookie
+/// database isn't read again until the key stops working
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CachedKey {
+    pub session_key: String,
+    pub created: Option<DateTime<Utc>>,
+    /// Name of the browser the key was read from
+    pub browser_name: String,
+    /// The browser selection the key was found with
+    pub browser: Option<String>,
+    pub profile: Option<String>,
+    pub container: Option<String>,
+}
+
+impl CachedKey {
+    /// Whether the key was found with the same browser, profile and container
+    pub fn matches(&self, selection: &cookies::BrowserSelection) -> bool {
+        self.browser.as_deref() == selection.browser
+            && self.profile.as_deref() == selection.profile
+            && self.container.as_deref() == selection.container
+    }
+}
+
+/// A session key stored in aochelper.toml or the secrets file
+pub struct StoredKey<'a> {
+    pub key: &'a String,
+    /// When the key was stored, or when the browser session it was imported
+    /// from was created
+    pub created: Option<DateTime<Utc>>,
+    pub store: KeyStore,
+}
+
+/// Where a session key is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStore {
+    /// The per-project aochelper.toml
+    Local,
+    /// The user's secrets file
+    User,
+}
+
+impl std::fmt::Display for KeyStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self, secrets_path()) {
+            (KeyStore::Local, _) => write!(f, "{}", config_file().display()),
+            (KeyStore::User, Some(path)) => write!(f, "{}", path.display()),
+            (KeyStore::User, None) => write!(f, "{SECRETS_FILE}"),
+        }
+    }
+}
+
+impl Config {
+    /// Apply the options for talking to the site: provider, server and
+    /// pacing. The client is built from [`Config::client_options`].
+    pub fn configure_requests(&self) -> Result<()> {
+        provider::set_configured(self.provider.as_deref())?;
+        aoc::set_base_url(self.base_url.clone());
+        aoc::set_request_interval(self.request_interval());
+        logging::set_log_file(self.log_file.clone());
+        Ok(())
+    }
+
+    /// The options to build the client from: proxy, timeouts, retries and
+    /// User-Agent contact
+    pub fn client_options(&self) -> aoc::ClientOptions {
+        aoc::ClientOptions {
+            timeouts: aoc::Timeouts {
+                connect: seconds(self.connect_timeout),
+                request: seconds(self.timeout),
+            },
+            proxy: self.proxy.clone(),
+            user_agent_contact: self.user_agent_contact.clone(),
+            retry_policy: self.retry_policy(),
+        }
+    }
+
+    pub fn request_interval(&self) -> Duration {
+        seconds(self.request_interval).unwrap_or(aoc::DEFAULT_REQUEST_INTERVAL)
+    }
+
+    /// Retry policy for requests, with defaults for options that aren't set
+    pub fn retry_policy(&self) -> aoc::RetryPolicy {
+        let default = aoc::RetryPolicy::DEFAULT;
+        aoc::RetryPolicy {
+            retries: self.retries.unwrap_or(default.retries),
+            delay: seconds(self.retry_delay).unwrap_or(default.delay),
+            jitter: seconds(self.retry_jitter).unwrap_or(default.jitter),
+        }
+    }
+
+    /// Select the account given on the command line, or else the default
+    /// account, if any. The account has to be configured unless `create` is
+    /// set, in which case it is added once something is stored for it.
+    pub fn select_account(&mut self, account: Option<&str>, create: bool) -> Result<()> {
+        let Some(name) = account.or(self.default_account.as_deref()) else {
+            return Ok(());
+        };
+        if !create && !self.has_account(name) {
+            return Err(unknown_account(name, &self.account_names()));
+        }
+        tracing::debug!("Using account '{name}'");
+        self.active_account = Some(name.to_string());
+        Ok(())
+    }
+
+    pub fn active_account(&self) -> Option<&str> {
+        self.active_account.as_deref()
+    }
+
+    fn has_account(&self, name: &str) -> bool {
+        self.accounts.contains_key(name) || self.secrets.accounts.contains_key(name)
+    }
+
+    fn account_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .accounts
+            .keys()
+            .chain(self.secrets.accounts.keys())
+            .map(String::as_str)
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Whether aochelper.toml holds a session key, stored with `--local`
+    fn has_local_session_key(&self) -> bool {
+        self.session_key.is_some()
+            || self
+                .accounts
+                .values()
+                .any(|account| account.session_key.is_some())
+    }
+
+    fn account(&self) -> Option<&Account> {
+        self.active_account
+            .as_ref()
+            .and_then(|name| self.accounts.get(name))
+    }
+
+    /// The session key of the selected account, or the top-level one if no
+    /// account is selected. A key stored with `--local` takes precedence over
+    /// the one in the secrets file.
+    pub fn session_key(&self) -> Option<StoredKey<'_>> {
+        [KeyStore::Local, KeyStore::User]
+            .into_iter()
+            .find_map(|store| {
+                let (key, created) = self.key_fields(store)?;
+                Some(StoredKey {
+                    key: key.as_ref()?,
+                    created,
+                    store,
+                })
+            })
+    }
+
+    /// Store or, if `key` is `None`, remove the session key of the selected
+    /// account in `store`. Returns whether a key was replaced or removed.
+    pub fn set_session_key(
+        &mut self,
+        store: KeyStore,
+        key: Option<String>,
+        created: Option<DateTime<Utc>>,
+    ) -> bool {
+        let (old_key, old_created) = self.key_fields_mut(store);
+        *old_created = created;
+        std::mem::replace(old_key, key).is_some()
+    }
+
+    fn key_fields(&self, store: KeyStore) -> Option<(&Option<String>, Option<DateTime<Utc>>)> {
+        match (&self.active_account, store) {
+            (Some(name), KeyStore::Local) => self
+                .accounts
+                .get(name)
+                .map(|a| (&a.session_key, a.session_key_created)),
+            (Some(name), KeyStore::User) => self
+                .secrets
+                .accounts
+                .get(name)
+                .map(|a| (&a.session_key, a.session_key_created)),
+            (None, KeyStore::Local) => Some((&self.session_key, self.session_key_created)),
+            (None, KeyStore::User) => {
+                Some((&self.secrets.session_key, self.secrets.session_key_created))
+            }
+        }
+    }
+
+    fn key_fields_mut(
+        &mut self,
+        store: KeyStore,
+    ) -> (&mut Option<String>, &mut Option<DateTime<Utc>>) {
+        match (&self.active_account, store) {
+            (Some(name), KeyStore::Local) => {
+                let a = self.accounts.entry(name.clone()).or_default();
+                (&mut a.session_key, &mut a.session_key_created)
+            }
+            (Some(name), KeyStore::User) => {
+                let a = self.secrets.accounts.entry(name.clone()).or_default();
+                (&mut a.session_key, &mut a.session_key_created)
+            }
+            (None, KeyStore::Local) => (&mut self.session_key, &mut self.session_key_created),
+            (None, KeyStore::User) => (
+                &mut self.secrets.session_key,
+                &mut self.secrets.session_key_created,
+            ),
+        }
+    }
+
+    /// The cached browser session key of the selected account
+    pub fn cached_key(&self) -> Option<&CachedKey> {
+        match &self.active_account {
+            Some(name) => self.secrets.accounts.get(name)?.cached.as_ref(),
+            None => self.secrets.cached.as_ref(),
+        }
+    }
+
+    /// Replace the cached browser session key of the selected account. Returns
+    /// whether a key was cached before.
+    pub fn set_cached_key(&mut self, cached: Option<CachedKey>) -> bool {
+        let old = match &self.active_account {
+            Some(name) => {
+                &mut self
+                    .secrets
+                    .accounts
+                    .entry(name.clone())
+                    .or_default()
+                    .cached
+            }
+            None => &mut self.secrets.cached,
+        };
+        std::mem::replace(old, cached).is_some()
+    }
+
+    /// The output path of the selected account, falling back to the top-level one
+    pub fn output_path(&self) -> Option<&PathBuf> {
+        self.account()
+            .and_then(|account| account.output_path.as_ref())
+            .or(self.output_path.as_ref())
+    }
+
+    /// The day in the name of the current directory, such as `day07`, `d07`
+    /// or `07-some-title`, or as picked out by the first group of
+    /// `day_pattern`
+    pub fn day_from_dir(&self) -> Result<Option<u8>> {
+        let pattern = day_pattern(self.day_pattern.as_deref().unwrap_or(DEFAULT_DAY_PATTERN))?;
+        let dir = env::current_dir()?;
+        let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+            return Ok(None);
+        };
+        let day = pattern
+            .captures(name)
+            .and_then(|captures| captures.get(1))
+            .and_then(|day| day.as_str().parse::<u8>().ok());
+        if let Some(day) = day {
+            tracing::debug!("Found day = {day} from the directory {name:?}");
+        }
+        Ok(day)
+    }
+
+    /// Where the solution of a day is, from `solution_path` with `{year}`,
+    /// `{day}` and `{day2}` (the day with two digits) filled in
+    pub fn solution_path(&self, year: u16, day: u8) -> PathBuf {
+        let pattern = self
+            .solution_path
+            .as_deref()
+            .unwrap_or(DEFAULT_SOLUTION_PATH);
+        PathBuf::from(fill_placeholders(pattern, &puzzle_placeholders(year, day)))
+    }
+
+    pub fn output_path_mut(&mut self) -> &mut Option<PathBuf> {
+        match &self.active_account {
+            Some(name) => &mut self.accounts.entry(name.clone()).or_default().output_path,
+            None => &mut self.output_path,
+        }
+    }
+
+    /// Write the file holding session keys in `store`
+    pub fn write_store(&self, store: KeyStore) -> Result<()> {
+        match store {
+            KeyStore::Local => write_config(self),
+            KeyStore::User => write_secrets(&self.secrets),
+        }
+    }
+}
+
+fn unknown_account(name: &str, names: &[&str]) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Unknown account '{name}'.{} Configured accounts: {}. Add it with `aochelper --account {name} set session_key <key>`.",
+        suggest::did_you_mean(name, names),
+        if names.is_empty() { "none".to_string() } else { names.join(", ") }
+    )
+}
+
+/// Location of the secrets file: `~/.config/aochelper/secrets.toml`, or
+/// `%APPDATA%\aochelper\secrets.toml` on Windows
+pub fn secrets_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else {
+        match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(config_dir.join("aochelper").join(SECRETS_FILE))
+}
+
+/// Directory for state aochelper keeps between runs:
+/// `~/.local/share/aochelper`, or `%LOCALAPPDATA%\aochelper` on Windows
+pub fn data_dir() -> Option<PathBuf> {
+    let data_dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("LOCALAPPDATA")?)
+    } else {
+        match env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?)
+                .join(".local")
+                .join("share"),
+        }
+    };
+    Some(data_dir.join("aochelper"))
+}
+
+/// Directory for files aochelper can download again if they are lost:
+/// `~/.cache/aochelper`, or `%LOCALAPPDATA%\aochelper\cache` on Windows
+pub fn cache_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return Some(data_dir()?.join("cache"));
+    }
+    let cache_dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache_dir.join("aochelper"))
+}
+
+fn read_secrets() -> Result<Secrets> {
+    match secrets_path() {
+        Some(path) if path.exists() => {
+            permissions::warn_if_exposed(&path);
+            let secrets_buf =
+                fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", &path))?;
+            let secrets: Secrets = toml::from_str(&secrets_buf)
+                .with_context(|| format!("Failed to parse {:?}", &path))?;
+            tracing::debug!("Read secrets from {:?}", path);
+            Ok(secrets)
+        }
+        _ => Ok(Secrets::default()),
+    }
+}
+
+pub fn write_secrets(secrets: &Secrets) -> Result<()> {
+    let path = secrets_path().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Could not find a user config directory for {SECRETS_FILE}. Use --local to store the session key in {} instead.",
+            config_file().display()
+        )
+    })?;
+    if dry_run::enabled() {
+        dry_run::would(format_args!("write {}", path.display()));
+        return Ok(());
+    }
+    let _span = tracing::info_span!("write", path = %path.display()).entered();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let secrets_toml = if secrets.locked {
+        // encrypt keys that were added since the file was locked
+        let mut secrets = secrets.clone();
+        vault::seal(&mut secrets, false)?;
+        toml::to_string(&secrets)?
+    } else {
+        toml::to_string(secrets)?
+    };
+    permissions::write_private(&path, secrets_toml.as_bytes())?;
+    tracing::debug!("Updated secrets file: {:?}", path);
+    Ok(())
+}
+
+pub fn read_config(config_path: PathBuf) -> Result<Config> {
+    if config_path.exists() {
+        let mut config_file = fs::File::open(&config_path)?;
+        let mut config_buf = String::new();
+        config_file.read_to_string(&mut config_buf)?;
+        let mut config: Config = toml::from_str(&config_buf)?;
+        tracing::debug!("Read configuration file from {:?}", config_file);
+        warn_unknown_keys(&config_path, &config_buf);
+        config.secrets = read_secrets()?;
+        Ok(config)
+    } else {
+        Ok(Config {
+            secrets: read_secrets()?,
+            ..Config::default()
+        })
+    }
+}
+
+/// Whether unknown keys were already mentioned, as the configuration is read
+/// more than once in a run
+static WARNED_UNKNOWN_KEYS: AtomicBool = AtomicBool::new(false);
+
+/// Warn about keys in the configuration file that aochelper doesn't know,
+/// which would otherwise be ignored without a word, typos especially
+fn warn_unknown_keys(config_path: &Path, config_buf: &str) {
+    let Ok(table) = config_buf.parse::<toml::Table>() else {
+        return;
+    };
+    let known: Vec<&str> = CONFIG_KEYS
+        .iter()
+        .copied()
+        .chain(["session_key_created", "hooks", "accounts"])
+        .collect();
+    let hooks = Hook::ALL.map(|hook| hook.id());
+    let unknown: Vec<(&String, &[&str])> = table
+        .keys()
+        .filter(|key| !known.contains(&key.as_str()))
+        .map(|key| (key, CONFIG_KEYS))
+        .chain(
+            table
+                .get("hooks")
+                .and_then(toml::Value::as_table)
+                .into_iter()
+                .flat_map(|table| table.keys())
+                .filter(|key| !hooks.contains(&key.as_str()))
+                .map(|key| (key, hooks.as_slice())),
+        )
+        .collect();
+    if unknown.is_empty() || WARNED_UNKNOWN_KEYS.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    for (key, candidates) in unknown {
+        let what = if candidates == CONFIG_KEYS {
+            "key"
+        } else {
+            "hook"
+        };
+        eprintln!(
+            "{} unknown {what} '{key}' in {} is ignored.{}",
+            color::err("Warning:", color::WARNING),
+            config_path.display(),
+            suggest::did_you_mean(key, candidates)
+        );
+    }
+}
+
+/// Configuration file given with --config, which takes precedence over
+/// $AOCHELPER_CONFIG
+static CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set_config_file_override(path: Option<PathBuf>) {
+    *CONFIG_FILE_OVERRIDE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = path;
+}
+
+/// The configuration file: the one given with --config or in
+/// $AOCHELPER_CONFIG, or else aochelper.toml in the current directory
+pub fn config_file() -> PathBuf {
+    let path = CONFIG_FILE_OVERRIDE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    path.or_else(|| {
+        env::var_os(CONFIG_ENV_VAR)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    })
+    .unwrap_or_else(|| PathBuf::from(CONFIG_FILE))
+}
+
+#[tracing::instrument(name = "write", skip_all, fields(path = %config_file().display()))]
+pub fn write_config(config: &Config) -> Result<()> {
+    let config_toml = toml::to_string(config)?;
+    let config_file = config_file();
+    if dry_run::enabled() {
+        dry_run::would(format_args!("write {}", config_file.display()));
+        return Ok(());
+    }
+    if config.has_local_session_key() {
+        permissions::write_private(&config_file, config_toml.as_bytes())?;
+    } else {
+        fs::write(&config_file, config_toml)
+            .with_context(|| format!("Failed to write {:?}", &config_file))?;
+    }
+    tracing::debug!("Updated local config file: {:?}", config_file);
+    Ok(())
+}
+
+/// A number of seconds from the config as a duration, if set and valid
+fn seconds(seconds: Option<f64>) -> Option<Duration> {
+    Duration::try_from_secs_f64(seconds?).ok()
+}
+
+/// Directory names that hold the puzzle of a day, unless set otherwise with
+/// `day_pattern`
+const DEFAULT_DAY_PATTERN: &str = r"(?i)^(?:day|d)?[-_ ]?(\d{1,2})(?:\D|$)";
+
+/// Where solutions are, unless set otherwise with `solution_path`: where
+/// cargo looks for binaries, so that `cargo run --bin day07` runs day 7
+const DEFAULT_SOLUTION_PATH: &str = "src/bin/day{day2}.rs";
+
+/// Values of the `{year}`, `{day}` and `{day2}` placeholders
+pub fn puzzle_placeholders(year: u16, day: u8) -> Vec<(&'static str, String)> {
+    vec![
+        ("year", year.to_string()),
+        ("day", day.to_string()),
+        ("day2", format!("{day:02}")),
+    ]
+}
+
+/// Replace `{name}` with the value of each of `vars`
+pub fn fill_placeholders(text: &str, vars: &[(&str, String)]) -> String {
+    vars.iter().fold(text.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), value)
+    })
+}
+
+fn day_pattern(pattern: &str) -> Result<Regex> {
+    let regex = Regex::new(pattern).with_context(|| format!("Invalid day_pattern {pattern:?}"))?;
+    if regex.captures_len() < 2 {
+        return Err(anyhow::anyhow!(
+            "day_pattern {pattern:?} needs a group, in parentheses, around the day."
+        ));
+    }
+    Ok(regex)
+}
+
+/// Parse a number of seconds for a config option
+fn parse_seconds(value: &str) -> Result<f64> {
+    let seconds = value.parse::<f64>()?;
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| anyhow::anyhow!("Invalid number of seconds: {value}"))?;
+    Ok(seconds)
+}
+
+/// Keys that can be given to `aochelper set`
+pub const CONFIG_KEYS: &[&str] = &[
+    "year",
+    "session_key",
+    "output_path",
+    "browser",
+    "profile",
+    "container",
+    "no_browser_cookie",
+    "provider",
+    "proxy",
+    "base_url",
+    "jobs",
+    "request_interval",
+    "user_agent_contact",
+    "day_pattern",
+    "retries",
+    "retry_delay",
+    "retry_jitter",
+    "connect_timeout",
+    "timeout",
+    "default_account",
+    "log_file",
+    "update_check",
+    "notifications",
+    "webhook_url",
+    "webhook_events",
+    "confirm_submit",
+    "save_examples",
+    "solution_path",
+    "solution_template",
+];
+
+/// Keys that can be given to `aochelper set`, hooks included
+pub fn settable_keys() -> Vec<&'static str> {
+    CONFIG_KEYS
+        .iter()
+        .copied()
+        .chain(Hook::ALL.map(|hook| hook.key()))
+        .collect()
+}
+
+/// Set a config option. The session key and output path are set for the given
+/// account, which is created if it doesn't exist yet. The session key goes into
+/// the user's secrets file unless `local` is set.
+pub fn set_config_option(
+    client: &aoc::Client,
+    key: &str,
+    value: &str,
+    verify: bool,
+    local: bool,
+    account: Option<&str>,
+) -> Result<()> {
+    if key == "session_key" {
+        logging::register_secret(value);
+    } else if local {
+        return Err(anyhow::anyhow!(
+            "--local can only be used with session_key."
+        ));
+    }
+    if verify {
+        if key != "session_key" {
+            return Err(anyhow::anyhow!(
+                "--verify can only be used with session_key."
+            ));
+        }
+        read_config(config_file())?.configure_requests()?;
+        if dry_run::enabled() {
+            dry_run::would(format_args!(
+                "check that the session key is logged in, at {}",
+                aoc::base_url()
+            ));
+        }
+        let user = aoc::check_session(client, value).map_err(|err| {
+            if dry_run::stopped(&err) {
+                err
+            } else {
+                anyhow::anyhow!("Refusing to store session key: {err}")
+            }
+        })?;
+        say!(
+            "{}",
+            color::say(
+                &format!("Session key is logged in as {user}."),
+                color::SUCCESS
+            )
+        );
+        report::set("user", &user);
+    }
+    let mut config = read_config(config_file())?;
+    config.select_account(account, true)?;
+
+    match key {
+        "year" => config.year = Some(value.parse::<u16>()?),
+        "session_key" => {
+            let store = if local {
+                KeyStore::Local
+            } else {
+                KeyStore::User
+            };
+            if dry_run::enabled() {
+                dry_run::would(format_args!("store the session key in {store}"));
+                return Ok(());
+            }
+            config.set_session_key(store, Some(value.to_string()), Some(Utc::now()));
+            config.write_store(store)?;
+            say!("Stored session key in {store}.");
+            report::set("key", key);
+            report::set("store", store.to_string());
+            return Ok(());
+        }
+        "output_path" => *config.output_path_mut() = Some(PathBuf::from(value)),
+        "browser" => {
+            cookies::validate_browser(value)?;
+            config.browser = Some(value.to_string())
+        }
+        "profile" => config.profile = Some(value.to_string()),
+        "container" => config.container = Some(value.to_string()),
+        "no_browser_cookie" => config.no_browser_cookie = Some(value.parse::<bool>()?),
+        "provider" => {
+            provider::find(value)?;
+            config.provider = Some(value.to_string())
+        }
+        "proxy" => config.proxy = Some(value.to_string()),
+        "base_url" => config.base_url = Some(value.trim_end_matches('/').to_string()),
+        "jobs" => config.jobs = Some(value.parse::<usize>()?.max(1)),
+        "request_interval" => config.request_interval = Some(parse_seconds(value)?),
+        "user_agent_contact" => config.user_agent_contact = Some(value.to_string()),
+        "day_pattern" => {
+            day_pattern(value)?;
+            config.day_pattern = Some(value.to_string())
+        }
+        "log_file" => config.log_file = Some(PathBuf::from(value)),
+        "update_check" => config.update_check = Some(value.parse::<bool>()?),
+        "notifications" => config.notifications = Some(value.parse::<bool>()?),
+        "webhook_url" => {
+            notify::validate_webhook_url(value)?;
+            config.webhook_url = Some(value.to_string())
+        }
+        "webhook_events" => config.webhook_events = Some(WebhookEvent::parse_list(value)?),
+        "confirm_submit" => config.confirm_submit = Some(value.parse::<bool>()?),
+        "save_examples" => config.save_examples = Some(value.parse::<bool>()?),
+        "solution_path" => config.solution_path = Some(value.to_string()),
+        "solution_template" => config.solution_template = Some(PathBuf::from(value)),
+        "retries" => config.retries = Some(value.parse::<u32>()?),
+        "retry_delay" => config.retry_delay = Some(parse_seconds(value)?),
+        "retry_jitter" => config.retry_jitter = Some(parse_seconds(value)?),
+        "connect_timeout" => config.connect_timeout = Some(parse_seconds(value)?),
+        "timeout" => config.timeout = Some(parse_seconds(value)?),
+        "default_account" => {
+            if !config.has_account(value) {
+                return Err(unknown_account(value, &config.account_names()));
+            }
+            config.default_account = Some(value.to_string())
+        }
+        _ if key.starts_with("hooks.") => {
+            let hook = Hook::parse(key.trim_start_matches("hooks."))?;
+            config.hooks.set(hook, value)
+        }
+        _ => {
+            let keys = settable_keys();
+            return Err(anyhow::anyhow!(
+                "Invalid key '{key}'.{} Valid keys are: {}",
+                suggest::did_you_mean(key, &keys),
+                keys.join(", ")
+            ));
+        }
+    }
+
+    if dry_run::enabled() {
+        dry_run::would(format_args!(
+            "set {key} = {value} in {}",
+            config_file().display()
+        ));
+        return Ok(());
+    }
+    write_config(&config)?;
+    tracing::debug!("Set {} = {}", key, value);
+    report::set("key", key);
+    report::set("value", value);
+
+    Ok(())
+}
@@ -0,0 +1,459 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::{cookie_host, cookie_name, with_cookie_db, BrowserCookie, CookieSource};
+
+/// A Chromium-based browser and where it keeps its cookies. All of these share
+/// the same cookie database format and encryption.
+pub struct ChromiumBrowser {
+    /// Name used to select this browser with `--browser`
+    pub id: &'static str,
+    pub name: &'static str,
+    /// User data directories relative to $HOME on Linux
+    linux_dirs: &'static [&'static str],
+    /// User data directories relative to $HOME on macOS
+    macos_dirs: &'static [&'static str],
+    /// User data directories on Windows, given as an environment variable
+    /// holding the base directory and a path relative to it
+    windows_dirs: &'static [(&'static str, &'static str)],
+    /// Subdirectory of the user data directory holding the default profile
+    default_profile: &'static str,
+    /// Name of the encryption password entry in KWallet and the macOS Keychain
+    #[cfg_attr(windows, allow(dead_code))]
+    safe_storage: &'static str,
+    /// Account of the encryption password entry in the macOS Keychain
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    keychain_account: &'static str,
+    /// `application` attribute of the libsecret entry holding the encryption password
+    #[cfg_attr(any(windows, target_os = "macos"), allow(dead_code))]
+    secret_app: &'static str,
+    /// Folder holding the encryption password in KWallet
+    #[cfg_attr(any(windows, target_os = "macos"), allow(dead_code))]
+    kwallet_folder: &'static str,
+}
+
+impl ChromiumBrowser {
+    fn user_data_dirs(&self) -> Result<Vec<PathBuf>> {
+        let dirs: Vec<(&str, &str)> = if cfg!(windows) {
+            self.windows_dirs.to_vec()
+        } else if cfg!(target_os = "macos") {
+            self.macos_dirs.iter().map(|dir| ("HOME", *dir)).collect()
+        } else {
+            self.linux_dirs.iter().map(|dir| ("HOME", *dir)).collect()
+        };
+        dirs.into_iter()
+            .map(|(base_var, dir)| {
+                let base_dir =
+                    env::var_os(base_var).with_context(|| format!("${base_var} is not set."))?;
+                Ok(Path::new(&base_dir).join(dir))
+            })
+            .collect()
+    }
+}
+
+/// Known Chromium-based browsers, in the order they are searched
+pub const CHROMIUM_BROWSERS: &[ChromiumBrowser] = &[
+    ChromiumBrowser {
+        id: "chrome",
+        name: "Chrome",
+        linux_dirs: &[".config/google-chrome"],
+        macos_dirs: &["Library/Application Support/Google/Chrome"],
+        windows_dirs: &[("LOCALAPPDATA", "Google/Chrome/User Data")],
+        default_profile: "Default",
+        safe_storage: "Chrome Safe Storage",
+        keychain_account: "Chrome",
+        secret_app: "chrome",
+        kwallet_folder: "Chrome Keys",
+    },
+    ChromiumBrowser {
+        id: "chromium",
+        name: "Chromium",
+        linux_dirs: &[".config/chromium", "snap/chromium/common/chromium"],
+        macos_dirs: &["Library/Application Support/Chromium"],
+        windows_dirs: &[("LOCALAPPDATA", "Chromium/User Data")],
+        default_profile: "Default",
+        safe_storage: "Chromium Safe Storage",
+        keychain_account: "Chromium",
+        secret_app: "chromium",
+        kwallet_folder: "Chromium Keys",
+    },
+    ChromiumBrowser {
+        id: "brave",
+        name: "Brave",
+        linux_dirs: &[
+            ".config/BraveSoftware/Brave-Browser",
+            "snap/brave/current/.config/BraveSoftware/Brave-Browser",
+        ],
+        macos_dirs: &["Library/Application Support/BraveSoftware/Brave-Browser"],
+        windows_dirs: &[("LOCALAPPDATA", "BraveSoftware/Brave-Browser/User Data")],
+        default_profile: "Default",
+        safe_storage: "Brave Safe Storage",
+        keychain_account: "Brave",
+        secret_app: "brave",
+        kwallet_folder: "Brave Keys",
+    },
+    ChromiumBrowser {
+        id: "edge",
+        name: "Microsoft Edge",
+        linux_dirs: &[".config/microsoft-edge"],
+        macos_dirs: &["Library/Application Support/Microsoft Edge"],
+        windows_dirs: &[("LOCALAPPDATA", "Microsoft/Edge/User Data")],
+        default_profile: "Default",
+        safe_storage: "Microsoft Edge Safe Storage",
+        keychain_account: "Microsoft Edge",
+        secret_app: "microsoft-edge",
+        kwallet_folder: "Microsoft Edge Keys",
+    },
+    ChromiumBrowser {
+        id: "vivaldi",
+        name: "Vivaldi",
+        linux_dirs: &[".config/vivaldi"],
+        macos_dirs: &["Library/Application Support/Vivaldi"],
+        windows_dirs: &[("LOCALAPPDATA", "Vivaldi/User Data")],
+        default_profile: "Default",
+        safe_storage: "Vivaldi Safe Storage",
+        keychain_account: "Vivaldi",
+        secret_app: "vivaldi",
+        kwallet_folder: "Vivaldi Keys",
+    },
+    ChromiumBrowser {
+        id: "opera",
+        name: "Opera",
+        linux_dirs: &[".config/opera"],
+        macos_dirs: &["Library/Application Support/com.operasoftware.Opera"],
+        windows_dirs: &[("APPDATA", "Opera Software/Opera Stable")],
+        // Opera keeps its only profile directly in the user data directory
+        default_profile: "",
+        safe_storage: "Opera Safe Storage",
+        keychain_account: "Opera",
+        secret_app: "opera",
+        kwallet_folder: "Opera Keys",
+    },
+];
+
+/// Cookie databases from this version on prefix the plaintext value with a
+/// SHA-256 digest of the host
+const HOST_DIGEST_DB_VERSION: i64 = 24;
+
+/// Location of a Chromium cookie database
+pub struct ChromiumCookies {
+    pub browser: &'static ChromiumBrowser,
+    pub db_path: PathBuf,
+    /// Directory holding `Local State`, the parent of all profiles
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub user_data_dir: PathBuf,
+}
+
+/// Chromium-based browsers, or only the one with the given id
+pub struct ChromiumSource<'a> {
+    pub browser: Option<&'a str>,
+}
+
+impl CookieSource for ChromiumSource<'_> {
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+        let cookies = find_chromium_cookie(self.browser)?;
+        let browser = cookies.browser.name;
+        tracing::debug!("Found {browser} cookies at {:?}", &cookies.db_path);
+        let cookie = read_chromium_host_cookie(&cookies, cookie_host())
+            .and_then(|cookie| cookie.check_expiry(browser))
+            .with_context(|| {
+                format!(
+                    "Failed to read {browser} cookies from {:?}",
+                    &cookies.db_path
+                )
+            })?;
+        Ok((cookie, browser.to_string()))
+    }
+}
+
+/// Find the Cookies database of the first installed Chromium-based browser, or
+/// of the browser with the given id.
+/// Only the default profile is currently supported.
+pub fn find_chromium_cookie(browser_id: Option<&str>) -> Result<ChromiumCookies> {
+    let mut searched = Vec::new();
+    for browser in CHROMIUM_BROWSERS
+        .iter()
+        .filter(|browser| browser_id.unwrap_or(browser.id) == browser.id)
+    {
+        for user_data_dir in browser
+            .user_data_dirs()
+            .context("Could not find Chromium cookies.")?
+        {
+            let profile = user_data_dir.join(browser.default_profile);
+            // newer versions keep the cookies in a Network subdirectory
+            for db_path in [
+                profile.join("Network").join("Cookies"),
+                profile.join("Cookies"),
+            ] {
+                if db_path.exists() {
+                    return Ok(ChromiumCookies {
+                        browser,
+                        db_path,
+                        user_data_dir,
+                    });
+                }
+            }
+            searched.push(user_data_dir);
+        }
+    }
+    Err(anyhow::anyhow!(
+        "Could not find cookies of any Chromium-based browser in {:?}.",
+        searched
+    ))
+}
+
+/// Convert a Chromium timestamp, in microseconds since 1601-01-01, into a time.
+/// Zero is used for cookies without an expiry date.
+fn chromium_time(micros: i64) -> Option<DateTime<Utc>> {
+    if micros == 0 {
+        return None;
+    }
+    let epoch = DateTime::parse_from_rfc3339("1601-01-01T00:00:00Z")
+        .ok()?
+        .with_timezone(&Utc);
+    epoch.checked_add_signed(Duration::microseconds(micros))
+}
+
+pub fn read_chromium_host_cookie(
+    cookies: &ChromiumCookies,
+    hostname: &str,
+) -> Result<BrowserCookie> {
+    let (value, encrypted_value, expires_utc, creation_utc, db_version) = with_cookie_db(
+        &cookies.db_path,
+        "chromium-cookies-tmp.sqlite",
+        |conn| {
+            let db_version: i64 = conn
+                .query_row("SELECT value FROM meta WHERE key='version'", [], |row| {
+                    row.get::<_, String>(0)
+                })
+                .ok()
+                .and_then(|version| version.parse().ok())
+                .unwrap_or(0);
+            let mut query = conn
+                .prepare(
+                    "SELECT value, encrypted_value, expires_utc, creation_utc FROM cookies
+                WHERE host_key=?1 AND name=?2",
+                )
+                .with_context(|| format!("Error with SQLite database connection {:?}.", &conn))?;
+            let mut res = query.query([hostname, cookie_name()])?;
+            match res.next()? {
+                Some(row) => Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    db_version,
+                )),
+                None => Err(anyhow::anyhow!(
+                "No cookie found for '{hostname}'. You may need to log in via the web browswer first."
+            )),
+            }
+        },
+    )?;
+
+    let value = if value.is_empty() {
+        let mut plaintext = decrypt::decrypt_value(cookies, &encrypted_value)?;
+        if db_version >= HOST_DIGEST_DB_VERSION && plaintext.len() >= 32 {
+            plaintext.drain(..32);
+        }
+        String::from_utf8(plaintext).context("Decrypted cookie is not valid UTF-8.")?
+    } else {
+        value
+    };
+    Ok(BrowserCookie {
+        value,
+        expires: chromium_time(expires_utc),
+        created: chromium_time(creation_utc),
+    })
+}
+
+/// Cookie decryption on Linux and macOS: AES-128-CBC with a key derived from a
+/// password stored in the desktop keyring or the macOS Keychain.
+#[cfg(not(windows))]
+mod decrypt {
+    use std::process::Command;
+
+    use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+    use anyhow::{Context, Result};
+    use sha1::Sha1;
+
+    use super::{ChromiumBrowser, ChromiumCookies};
+
+    type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+    #[cfg(target_os = "macos")]
+    const KEY_ITERATIONS: u32 = 1003;
+    #[cfg(not(target_os = "macos"))]
+    const KEY_ITERATIONS: u32 = 1;
+
+    /// Password Chromium uses on Linux when no keyring is available
+    #[cfg(not(target_os = "macos"))]
+    const FALLBACK_PASSWORD: &[u8] = b"peanuts";
+
+    fn command_output(command: &mut Command) -> Option<Vec<u8>> {
+        let output = command.output().ok()?;
+        let password = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !password.is_empty() {
+            Some(password.into_bytes())
+        } else {
+            None
+        }
+    }
+
+    /// Look up the cookie encryption password in the desktop keyring.
+    /// GNOME Keyring and other libsecret providers are queried via `secret-tool`,
+    /// KWallet via `kwallet-query`.
+    #[cfg(not(target_os = "macos"))]
+    fn keyring_password(browser: &ChromiumBrowser) -> Option<Vec<u8>> {
+        command_output(Command::new("secret-tool").args([
+            "lookup",
+            "application",
+            browser.secret_app,
+        ]))
+        .or_else(|| {
+            command_output(
+                Command::new("kwallet-query")
+                    .args(["-r", browser.safe_storage])
+                    .args(["-f", browser.kwallet_folder])
+                    .arg("kdewallet"),
+            )
+        })
+    }
+
+    /// Look up the cookie encryption password in the login Keychain. macOS may
+    /// ask the user to allow access.
+    #[cfg(target_os = "macos")]
+    fn keyring_password(browser: &ChromiumBrowser) -> Option<Vec<u8>> {
+        command_output(Command::new("security").args([
+            "find-generic-password",
+            "-w",
+            "-s",
+            browser.safe_storage,
+            "-a",
+            browser.keychain_account,
+        ]))
+    }
+
+    fn password(browser: &ChromiumBrowser, scheme: &[u8]) -> Result<Vec<u8>> {
+        match scheme {
+            #[cfg(not(target_os = "macos"))]
+            b"v10" => return Ok(FALLBACK_PASSWORD.to_vec()),
+            #[cfg(not(target_os = "macos"))]
+            b"v11" => {}
+            #[cfg(target_os = "macos")]
+            b"v10" => {}
+            _ => return Err(anyhow::anyhow!("Unknown cookie encryption scheme.")),
+        }
+        keyring_password(browser).with_context(|| {
+            format!(
+                "Could not find the {} password in the system keyring.",
+                browser.safe_storage
+            )
+        })
+    }
+
+    fn derive_key(password: &[u8]) -> [u8; 16] {
+        let mut key = [0u8; 16];
+        pbkdf2::pbkdf2_hmac::<Sha1>(password, b"saltysalt", KEY_ITERATIONS, &mut key);
+        key
+    }
+
+    pub fn decrypt_value(cookies: &ChromiumCookies, encrypted_value: &[u8]) -> Result<Vec<u8>> {
+        let scheme = encrypted_value.get(..3).unwrap_or_default();
+        let password = password(cookies.browser, scheme)?;
+        Aes128CbcDec::new(&derive_key(&password).into(), &[b' '; 16].into())
+            .decrypt_padded_vec_mut::<Pkcs7>(&encrypted_value[3..])
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt cookie value."))
+    }
+}
+
+/// Cookie decryption on Windows: AES-256-GCM with a key stored in `Local State`,
+/// itself encrypted with DPAPI for the current user.
+#[cfg(windows)]
+mod decrypt {
+    use std::fs;
+    use std::ptr;
+
+    use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+    use anyhow::{Context, Result};
+    use base64::Engine;
+    use windows_sys::Win32::Foundation::LocalFree;
+    use windows_sys::Win32::Security::Cryptography::{CryptUnprotectData, CRYPT_INTEGER_BLOB};
+
+    use super::ChromiumCookies;
+
+    fn dpapi_unprotect(data: &[u8]) -> Result<Vec<u8>> {
+        let data_in = CRYPT_INTEGER_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        };
+        let mut data_out = CRYPT_INTEGER_BLOB {
+            cbData: 0,
+            pbData: ptr::null_mut(),
+        };
+        // SAFETY: data_in points to a live buffer of the given length, and the
+        // output buffer allocated by DPAPI is copied and then freed exactly once.
+        unsafe {
+            if CryptUnprotectData(
+                &data_in,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                0,
+                &mut data_out,
+            ) == 0
+            {
+                return Err(anyhow::anyhow!("DPAPI failed to decrypt the cookie key."));
+            }
+            let plaintext =
+                std::slice::from_raw_parts(data_out.pbData, data_out.cbData as usize).to_vec();
+            LocalFree(data_out.pbData as _);
+            Ok(plaintext)
+        }
+    }
+
+    fn master_key(cookies: &ChromiumCookies) -> Result<Vec<u8>> {
+        let local_state_path = cookies.user_data_dir.join("Local State");
+        let local_state: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(&local_state_path)
+                .with_context(|| format!("Failed to read {:?}", &local_state_path))?,
+        )?;
+        let encoded = local_state["os_crypt"]["encrypted_key"]
+            .as_str()
+            .with_context(|| format!("No encrypted key in {:?}", &local_state_path))?;
+        let encrypted_key = base64::engine::general_purpose::STANDARD.decode(encoded)?;
+        let encrypted_key = encrypted_key
+            .strip_prefix(b"DPAPI")
+            .context("Unknown cookie key encryption scheme.")?;
+        dpapi_unprotect(encrypted_key)
+    }
+
+    pub fn decrypt_value(cookies: &ChromiumCookies, encrypted_value: &[u8]) -> Result<Vec<u8>> {
+        match encrypted_value.get(..3) {
+            Some(b"v10") => {}
+            Some(b"v20") => {
+                return Err(anyhow::anyhow!(
+                    "{} uses app-bound encryption for this cookie, which can't be read by other programs.",
+                    cookies.browser.name
+                ))
+            }
+            // cookies written by old versions are encrypted with DPAPI directly
+            _ => return dpapi_unprotect(encrypted_value),
+        }
+        let payload = &encrypted_value[3..];
+        if payload.len() < 12 {
+            return Err(anyhow::anyhow!("Encrypted cookie value is truncated."));
+        }
+        let (nonce, ciphertext) = payload.split_at(12);
+        let key = master_key(cookies)?;
+        Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| anyhow::anyhow!("Invalid cookie key length."))?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt cookie value."))
+    }
+}
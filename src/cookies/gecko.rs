@@ -0,0 +1,419 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use glob::glob;
+
+use chrono::{DateTime, Utc};
+
+use super::{cookie_host, cookie_name, with_cookie_db, BrowserCookie, CookieSource};
+
+pub const COOKIE_GLOB: &str =
+    "/home/*/snap/firefox/common/.mozilla/firefox/*.default/cookies.sqlite";
+
+/// A browser built on Gecko, which keeps its profiles in a directory with a
+/// profiles.ini and its cookies in `moz_cookies` tables.
+pub struct GeckoBrowser {
+    /// Name used to select this browser with `--browser`
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Profile directories relative to $HOME on Linux
+    linux_dirs: &'static [&'static str],
+    /// Profile directories relative to $HOME on macOS
+    macos_dirs: &'static [&'static str],
+    /// Profile directories relative to %APPDATA% on Windows
+    windows_dirs: &'static [&'static str],
+}
+
+impl GeckoBrowser {
+    fn profile_dirs(&self) -> Result<Vec<PathBuf>> {
+        let (base_var, dirs) = if cfg!(windows) {
+            ("APPDATA", self.windows_dirs)
+        } else if cfg!(target_os = "macos") {
+            ("HOME", self.macos_dirs)
+        } else {
+            ("HOME", self.linux_dirs)
+        };
+        let base_dir = env::var_os(base_var).with_context(|| format!("${base_var} is not set."))?;
+        Ok(dirs
+            .iter()
+            .map(|dir| Path::new(&base_dir).join(dir))
+            .collect())
+    }
+}
+
+/// Known Gecko-based browsers, in the order they are searched. Firefox ESR and
+/// Developer Edition share the Firefox directory, each with its own default
+/// profile. On Linux, Snap is tried first, then the distribution package or
+/// tarball, then Flatpak.
+pub const GECKO_BROWSERS: &[GeckoBrowser] = &[
+    GeckoBrowser {
+        id: "firefox",
+        name: "Firefox",
+        linux_dirs: &[
+            "snap/firefox/common/.mozilla/firefox",
+            ".mozilla/firefox",
+            ".var/app/org.mozilla.firefox/.mozilla/firefox",
+        ],
+        macos_dirs: &["Library/Application Support/Firefox"],
+        windows_dirs: &["Mozilla/Firefox"],
+    },
+    GeckoBrowser {
+        id: "librewolf",
+        name: "LibreWolf",
+        linux_dirs: &[
+            ".librewolf",
+            ".var/app/io.gitlab.librewolf-community/.librewolf",
+        ],
+        macos_dirs: &["Library/Application Support/librewolf"],
+        windows_dirs: &["librewolf"],
+    },
+    GeckoBrowser {
+        id: "waterfox",
+        name: "Waterfox",
+        linux_dirs: &[".waterfox", ".var/app/net.waterfox.waterfox/.waterfox"],
+        macos_dirs: &["Library/Application Support/Waterfox"],
+        windows_dirs: &["Waterfox"],
+    },
+];
+
+/// Location of a Gecko cookie database
+pub struct GeckoCookies {
+    pub browser: &'static GeckoBrowser,
+    pub db_path: PathBuf,
+}
+
+/// Firefox and other Gecko-based browsers, optionally restricted to one
+/// browser, profile and container
+pub struct GeckoSource<'a> {
+    pub browser: Option<&'a str>,
+    pub profile: Option<&'a str>,
+    pub container: Option<&'a str>,
+}
+
+impl CookieSource for GeckoSource<'_> {
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+        let cookies = find_gecko_cookie(self.browser, self.profile)?;
+        let browser = cookies.browser.name;
+        tracing::debug!("Found {browser} cookies at {:?}", &cookies.db_path);
+        let cookie = read_ff_host_cookie(&cookies.db_path, cookie_host(), self.container)
+            .and_then(|cookie| cookie.check_expiry(browser))
+            .with_context(|| {
+                format!(
+                    "Failed to read {browser} cookies from {:?}",
+                    &cookies.db_path
+                )
+            })?;
+        Ok((cookie, browser.to_string()))
+    }
+}
+
+/// Find the cookies.sqlite file of the first installed Gecko-based browser, or
+/// of the browser with the given id.
+/// Snap-installed Firefox is tried first on Linux, followed by the default
+/// profiles listed in profiles.ini of each browser's profile directories. If a
+/// profile name is given, only profiles with that name are considered.
+pub fn find_gecko_cookie(
+    browser_id: Option<&str>,
+    profile_name: Option<&str>,
+) -> Result<GeckoCookies> {
+    let mut errors = Vec::new();
+    if cfg!(target_os = "linux")
+        && browser_id.unwrap_or("firefox") == "firefox"
+        && profile_name.is_none()
+    {
+        match find_snap_cookie(COOKIE_GLOB) {
+            Ok(db_path) => {
+                return Ok(GeckoCookies {
+                    browser: &GECKO_BROWSERS[0],
+                    db_path,
+                })
+            }
+            Err(err) => errors.push(err.to_string()),
+        }
+    }
+    let mut found_profile_dir = false;
+    for browser in GECKO_BROWSERS
+        .iter()
+        .filter(|browser| browser_id.unwrap_or(browser.id) == browser.id)
+    {
+        for profile_dir in browser
+            .profile_dirs()
+            .context("Could not find Firefox cookies.")?
+        {
+            if !profile_dir.exists() {
+                continue;
+            }
+            found_profile_dir = true;
+            match find_profiles(&profile_dir, profile_name) {
+                Ok(profiles) => {
+                    for profile in profiles {
+                        let db_path = profile.join("cookies.sqlite");
+                        if db_path.exists() {
+                            return Ok(GeckoCookies { browser, db_path });
+                        }
+                        errors.push(format!("{db_path:?} does not exist."));
+                    }
+                }
+                Err(err) => errors.push(format!("{err:#}")),
+            }
+        }
+    }
+    if !found_profile_dir {
+        errors.push("No Firefox, LibreWolf or Waterfox profile directories found.".to_string());
+    }
+    Err(anyhow::anyhow!(
+        "Could not find Firefox cookies. {}",
+        errors.join("; ")
+    ))
+}
+
+/// Find cookies.sqlite for Firefox installed via Snap.
+/// Only the default profile is currently supported
+fn find_snap_cookie(cookie_glob: &str) -> Result<PathBuf> {
+    // glob pattern is hard-coded, so single run should be enough to prove
+    // that this can't fail
+    let mut gb = glob(cookie_glob).expect("Failed to read glob pattern");
+    match gb.next() {
+        Some(path) => Ok(path.expect("Error with file path")),
+        None => Err(anyhow::anyhow!("No matches for {cookie_glob}.")),
+    }
+}
+
+/// A `[Section]` of an ini file and its key-value pairs
+type IniSection = (String, Vec<(String, String)>);
+
+fn parse_ini(contents: &str) -> Vec<IniSection> {
+    let mut sections: Vec<IniSection> = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.to_string(), Vec::new()));
+        } else if let (Some((key, value)), Some((_, entries))) =
+            (line.split_once('='), sections.last_mut())
+        {
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    sections
+}
+
+fn ini_get<'a>(entries: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    entries
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Read profiles.ini in `profile_dir` and return the directories of the
+/// profiles called `profile_name`, or of the default profiles if no name is
+/// given, most preferred first.
+///
+/// Each installation (such as release, ESR and Developer Edition) locks its own
+/// default profile; these come before the profile marked `Default=1`. If
+/// neither is present, the first profile listed is used.
+fn find_profiles(profile_dir: &Path, profile_name: Option<&str>) -> Result<Vec<PathBuf>> {
+    let ini_path = profile_dir.join("profiles.ini");
+    let contents =
+        fs::read_to_string(&ini_path).with_context(|| format!("Failed to read {:?}", &ini_path))?;
+    let sections = parse_ini(&contents);
+
+    let profiles: Vec<&Vec<(String, String)>> = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Profile"))
+        .map(|(_, entries)| entries)
+        .collect();
+    let selected: Vec<&Vec<(String, String)>> = match profile_name {
+        Some(profile_name) => {
+            let named: Vec<_> = profiles
+                .iter()
+                .filter(|entries| ini_get(entries, "Name") == Some(profile_name))
+                .copied()
+                .collect();
+            if named.is_empty() {
+                let names: Vec<&str> = profiles
+                    .iter()
+                    .filter_map(|entries| ini_get(entries, "Name"))
+                    .collect();
+                return Err(anyhow::anyhow!(
+                    "No profile named '{profile_name}' in {:?}. Available profiles: {}",
+                    &ini_path,
+                    names.join(", ")
+                ));
+            }
+            named
+        }
+        None => {
+            let mut defaults: Vec<_> = sections
+                .iter()
+                .filter(|(name, _)| name.starts_with("Install"))
+                .filter_map(|(_, entries)| ini_get(entries, "Default"))
+                .filter_map(|path| {
+                    profiles
+                        .iter()
+                        .find(|entries| ini_get(entries, "Path") == Some(path))
+                        .copied()
+                })
+                .collect();
+            defaults.extend(
+                profiles
+                    .iter()
+                    .find(|entries| ini_get(entries, "Default") == Some("1")),
+            );
+            if defaults.is_empty() {
+                defaults.extend(profiles.first());
+            }
+            if defaults.is_empty() {
+                return Err(anyhow::anyhow!("No profiles listed in {:?}", &ini_path));
+            }
+            defaults
+        }
+    };
+
+    let mut paths = Vec::new();
+    for profile in selected {
+        let path = ini_get(profile, "Path")
+            .with_context(|| format!("Profile without a path in {:?}", &ini_path))?;
+        let path = if ini_get(profile, "IsRelative") == Some("0") {
+            PathBuf::from(path)
+        } else {
+            profile_dir.join(path)
+        };
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Names of the containers that come with Firefox, which are stored in
+/// containers.json by localization ID rather than by name
+const BUILTIN_CONTAINERS: &[(&str, &str)] = &[
+    ("userContextPersonal.label", "Personal"),
+    ("userContextWork.label", "Work"),
+    ("userContextBanking.label", "Banking"),
+    ("userContextShopping.label", "Shopping"),
+];
+
+/// Look up the user context ID of a Multi-Account Container by name in the
+/// profile's containers.json
+fn container_id(profile: &Path, container: &str) -> Result<u64> {
+    let containers_path = profile.join("containers.json");
+    let containers: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(&containers_path)
+            .with_context(|| format!("Failed to read {:?}", &containers_path))?,
+    )
+    .with_context(|| format!("Failed to parse {:?}", &containers_path))?;
+    let identities = containers["identities"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let name_of = |identity: &serde_json::Value| -> Option<String> {
+        identity["name"].as_str().map(str::to_string).or_else(|| {
+            let l10n_id = identity["l10nID"].as_str()?;
+            BUILTIN_CONTAINERS
+                .iter()
+                .find(|(id, _)| *id == l10n_id)
+                .map(|(_, name)| name.to_string())
+        })
+    };
+    let public = identities
+        .iter()
+        .filter(|identity| identity["public"].as_bool().unwrap_or(true));
+    for identity in public.clone() {
+        if name_of(identity).is_some_and(|name| name.eq_ignore_ascii_case(container)) {
+            return identity["userContextId"]
+                .as_u64()
+                .with_context(|| format!("Container '{container}' has no ID"));
+        }
+    }
+    let names: Vec<String> = public.filter_map(name_of).collect();
+    Err(anyhow::anyhow!(
+        "No container named '{container}' in {:?}. Available containers: {}",
+        &containers_path,
+        names.join(", ")
+    ))
+}
+
+/// Extract the container a cookie belongs to from its `originAttributes`,
+/// e.g. `^userContextId=2&partitionKey=...`. Cookies outside of any container
+/// have an empty value.
+fn user_context_id(origin_attributes: &str) -> Option<u64> {
+    origin_attributes
+        .trim_start_matches('^')
+        .split('&')
+        .find_map(|attr| attr.strip_prefix("userContextId="))
+        .and_then(|id| id.parse().ok())
+}
+
+/// Convert the `expiry` column of `moz_cookies` into a time. Firefox used to
+/// store seconds since the Unix epoch, newer versions store milliseconds.
+fn expiry_time(expiry: i64) -> Option<DateTime<Utc>> {
+    if expiry > 100_000_000_000 {
+        DateTime::from_timestamp_millis(expiry)
+    } else {
+        DateTime::from_timestamp(expiry, 0)
+    }
+}
+
+/// Read the session cookie for `hostname` from a Firefox cookie database.
+/// If a container is named, only cookies from that container are considered.
+/// Otherwise cookies outside of any container are preferred, falling back to
+/// the most recently used container cookie.
+pub fn read_ff_host_cookie(
+    db_path: &Path,
+    hostname: &str,
+    container: Option<&str>,
+) -> Result<BrowserCookie> {
+    let container_id = match container {
+        Some(container) => {
+            let profile = db_path.parent().unwrap_or(Path::new("."));
+            Some(container_id(profile, container)?)
+        }
+        None => None,
+    };
+    // Not finding the cookie is an error rather than an empty result, so that
+    // a cookie only in the write-ahead log is looked for in a copy of it
+    with_cookie_db(db_path, "cookies-tmp.sqlite", |conn| {
+        let mut query = conn
+            .prepare(
+                "SELECT value, originAttributes, expiry, creationTime FROM moz_cookies
+                WHERE host=?1 AND name=?2
+                ORDER BY lastAccessed DESC",
+            )
+            .with_context(|| format!("Error with SQLite database connection {:?}.", &conn))?;
+        let cookies = query
+            .query_map([hostname, cookie_name()], |row| {
+                let cookie = BrowserCookie {
+                    value: row.get(0)?,
+                    expires: expiry_time(row.get(2)?),
+                    // microseconds since the Unix epoch
+                    created: DateTime::from_timestamp_micros(row.get(3)?),
+                };
+                Ok((cookie, row.get(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(BrowserCookie, String)>>>()?;
+
+        let index = match container_id {
+            Some(id) => cookies
+                .iter()
+                .position(|(_, attrs)| user_context_id(attrs) == Some(id)),
+            None => cookies
+                .iter()
+                .position(|(_, attrs)| user_context_id(attrs).is_none())
+                .or_else(|| (!cookies.is_empty()).then_some(0)),
+        };
+        match (index, container) {
+            (Some(index), _) => Ok(cookies.into_iter().nth(index).unwrap().0),
+            (None, Some(container)) => Err(anyhow::anyhow!(
+                "No cookie found for '{hostname}' in container '{container}'. You may need to log in via the web browswer first."
+            )),
+            (None, None) => Err(anyhow::anyhow!(
+                "No cookie found for '{hostname}'. You may need to log in via the web browswer first."
+            )),
+        }
+    })
+}
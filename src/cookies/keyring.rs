@@ -0,0 +1,73 @@
+use std::io::ErrorKind;
+use std::process::Command;
+
+use anyhow::Result;
+
+use super::{BrowserCookie, CookieSource};
+
+/// Service under which the session key is stored in the keyring
+pub const KEYRING_SERVICE: &str = "aochelper";
+
+/// Session key stored in the system keyring, under [`KEYRING_SERVICE`] and the
+/// name of the account: libsecret (GNOME Keyring, KWallet) through
+/// `secret-tool` on Linux, and the login Keychain through `security` on macOS
+pub struct KeyringSource<'a> {
+    pub account: &'a str,
+}
+
+impl CookieSource for KeyringSource<'_> {
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+        let mut command = lookup_command(self.account)?;
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Err(anyhow::anyhow!(
+                    "The system keyring can't be read, as {program} is not installed."
+                ))
+            }
+            Err(err) => return Err(anyhow::anyhow!("Failed to run {program}: {err}")),
+        };
+        let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || value.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No session key for account '{}' in the system keyring.",
+                self.account
+            ));
+        }
+        let cookie = BrowserCookie {
+            value,
+            expires: None,
+            created: None,
+        };
+        Ok((cookie, "the system keyring".to_string()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn lookup_command(account: &str) -> Result<Command> {
+    let mut command = Command::new("security");
+    command.args([
+        "find-generic-password",
+        "-w",
+        "-s",
+        KEYRING_SERVICE,
+        "-a",
+        account,
+    ]);
+    Ok(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn lookup_command(account: &str) -> Result<Command> {
+    let mut command = Command::new("secret-tool");
+    command.args(["lookup", "service", KEYRING_SERVICE, "account", account]);
+    Ok(command)
+}
+
+#[cfg(windows)]
+fn lookup_command(_account: &str) -> Result<Command> {
+    Err(anyhow::anyhow!(
+        "The system keyring is only read on Linux and macOS."
+    ))
+}
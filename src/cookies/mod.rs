@@ -0,0 +1,449 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{aoc, permissions, provider, suggest};
+
+pub mod chromium;
+pub mod gecko;
+pub mod keyring;
+pub mod netscape;
+pub mod safari;
+
+/// Host under which the site stores its session cookie
+fn cookie_host() -> &'static str {
+    provider::active().cookie_host()
+}
+
+/// Name of the session cookie
+fn cookie_name() -> &'static str {
+    provider::active().cookie_name()
+}
+
+const SAFARI_ID: &str = "safari";
+
+/// Browser names accepted by `--browser` and the `browser` config key
+pub fn browser_ids() -> Vec<&'static str> {
+    gecko::GECKO_BROWSERS
+        .iter()
+        .map(|browser| browser.id)
+        .chain(chromium::CHROMIUM_BROWSERS.iter().map(|browser| browser.id))
+        .chain([SAFARI_ID])
+        .collect()
+}
+
+/// Check that `browser` names a supported browser
+pub fn validate_browser(browser: &str) -> Result<()> {
+    let ids = browser_ids();
+    if ids.contains(&browser) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Unknown browser '{browser}'.{} Supported browsers are: {}",
+            suggest::did_you_mean(browser, &ids),
+            ids.join(", ")
+        ))
+    }
+}
+
+/// A session cookie as stored by a browser
+pub struct BrowserCookie {
+    pub value: String,
+    /// `None` for cookies that expire when the browser is closed
+    pub expires: Option<DateTime<Utc>>,
+    pub created: Option<DateTime<Utc>>,
+}
+
+impl BrowserCookie {
+    /// Return the cookie, or an error if it has already expired.
+    fn check_expiry(self, browser: &str) -> Result<Self> {
+        if let Some(created) = self.created {
+            tracing::debug!(
+                "{browser} session cookie was created on {} ({} days ago)",
+                created.format("%Y-%m-%d"),
+                (Utc::now() - created).num_days()
+            );
+        }
+        match self.expires {
+            Some(expires) if expires <= Utc::now() => Err(anyhow::anyhow!(
+                "Your Advent of Code session cookie in {browser} expired on {}. Log into {} again.",
+                expires.format("%Y-%m-%d %H:%M UTC"),
+                aoc::site()
+            )),
+            _ => Ok(self),
+        }
+    }
+}
+
+/// Run a query against a browser cookie database.
+///
+/// The database is opened read-only as immutable, so SQLite neither takes locks
+/// (which the running browser holds) nor writes anything. An immutable database
+/// ignores its write-ahead log however, so if the query fails and there is a
+/// log, or if the database can't be opened this way, we fall back to querying
+/// a temporary copy of the database and its log. Queries should therefore fail
+/// when they find nothing, as what they look for may only be in the log.
+fn with_cookie_db<T>(
+    db_path: &Path,
+    tmp_name: &str,
+    query: impl Fn(&Connection) -> Result<T>,
+) -> Result<T> {
+    match Connection::open_with_flags(
+        immutable_uri(db_path),
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    ) {
+        Ok(conn) => match query(&conn) {
+            Ok(result) => return Ok(result),
+            Err(err) if !wal_path(db_path).exists() => return Err(err),
+            Err(err) => {
+                tracing::debug!("Reading a copy of {db_path:?} and its log instead: {err:#}")
+            }
+        },
+        Err(err) => tracing::debug!("Failed to open {db_path:?} as immutable: {err}"),
+    }
+    with_db_copy(db_path, tmp_name, query)
+}
+
+fn wal_path(db_path: &Path) -> PathBuf {
+    let mut wal = db_path.as_os_str().to_owned();
+    wal.push("-wal");
+    PathBuf::from(wal)
+}
+
+/// Build an SQLite URI opening `db_path` as immutable
+fn immutable_uri(db_path: &Path) -> String {
+    let mut path = db_path.to_string_lossy().replace('\\', "/");
+    if !path.starts_with('/') {
+        // Windows paths such as C:/Users need an empty authority
+        path.insert(0, '/');
+    }
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        match byte {
+            b'?' | b'#' | b'%' | b' ' => uri.push_str(&format!("%{byte:02X}")),
+            _ => uri.push(byte as char),
+        }
+    }
+    uri.push_str("?immutable=1");
+    uri
+}
+
+/// Prefix of the temporary directories cookie databases are copied to
+pub const TEMP_DIR_PREFIX: &str = "aochelper-";
+
+/// Run a query against a temporary copy of a browser cookie database and its
+/// write-ahead log, if any. The copies are made in a new temporary directory
+/// that only the current user can access, which is removed when this returns,
+/// even on failure.
+fn with_db_copy<T>(
+    db_path: &Path,
+    tmp_name: &str,
+    query: impl Fn(&Connection) -> Result<T>,
+) -> Result<T> {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix(TEMP_DIR_PREFIX)
+        .tempdir()
+        .context("Failed to create temporary directory for cookie database")?;
+    let tmp_db_path = tmp_dir.path().join(tmp_name);
+    fs::copy(db_path, &tmp_db_path)
+        .with_context(|| format!("Failed to copy from {:?} to {:?}", &db_path, &tmp_db_path))?;
+    permissions::restrict(&tmp_db_path)?;
+    if wal_path(db_path).exists() {
+        let tmp_wal_path = wal_path(&tmp_db_path);
+        fs::copy(wal_path(db_path), &tmp_wal_path).with_context(|| {
+            format!(
+                "Failed to copy from {:?} to {:?}",
+                wal_path(db_path),
+                &tmp_wal_path
+            )
+        })?;
+        permissions::restrict(&tmp_wal_path)?;
+    }
+
+    let result = {
+        // inner scope such that DB connection will be closed before temporary file is
+        // deleted
+        let conn = Connection::open_with_flags(
+            &tmp_db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_context(|| format!("Failed to open database connection to {:?}.", &tmp_db_path))?;
+        query(&conn)
+    };
+    let tmp_dir_path = tmp_dir.path().to_path_buf();
+    if let Err(err) = tmp_dir.close() {
+        tracing::warn!("Unable to remove {:?}: {:?}", &tmp_dir_path, err);
+    }
+    result
+}
+
+/// A place the Advent of Code session cookie can be read from
+pub trait CookieSource {
+    /// Read the session cookie. Returns it along with the name of the browser
+    /// or file it was found in.
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)>;
+}
+
+/// Environment variable holding a session key, as used by other Advent of Code
+/// tools
+pub const SESSION_ENV_VAR: &str = "AOC_SESSION";
+
+/// Session key from the `AOC_SESSION` environment variable
+pub struct EnvSource;
+
+impl CookieSource for EnvSource {
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+        let value = env::var(SESSION_ENV_VAR)
+            .ok()
+            .map(|value| {
+                value
+                    .trim()
+                    .trim_start_matches(&format!("{}=", cookie_name()))
+                    .to_string()
+            })
+            .filter(|value| !value.is_empty())
+            .with_context(|| format!("${SESSION_ENV_VAR} is not set."))?;
+        let cookie = BrowserCookie {
+            value,
+            expires: None,
+            created: None,
+        };
+        Ok((cookie, format!("${SESSION_ENV_VAR}")))
+    }
+}
+
+/// Read the Advent of Code session key from a Netscape cookies.txt file.
+pub fn read_cookie_file(cookie_path: &Path) -> Result<(BrowserCookie, String)> {
+    netscape::NetscapeSource(cookie_path).read_cookie()
+}
+
+/// Which browser cookies are read from
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BrowserSelection<'a> {
+    /// Browser ID, or any supported browser if `None`
+    pub browser: Option<&'a str>,
+    /// Name of a Firefox profile, instead of the default one
+    pub profile: Option<&'a str>,
+    /// Name of a Firefox Multi-Account Container
+    pub container: Option<&'a str>,
+    /// Don't look for browser cookies at all, e.g. on servers and in CI
+    pub disabled: bool,
+}
+
+/// Find the Advent of Code session key in the selected browser, or in any
+/// supported browser if none is selected. Returns the session cookie and the
+/// name of the browser it was found in.
+/// Firefox and other Gecko-based browsers are tried first, followed by
+/// Chromium-based browsers, and Safari on macOS. Profiles and containers can
+/// only be selected for Gecko-based browsers, so no others are searched then.
+pub fn find_session_key(selection: BrowserSelection) -> Result<(BrowserCookie, String)> {
+    if selection.disabled {
+        return Err(NoSessionKey(
+            "No session configured, and reading browser cookies is disabled. \
+             Run `aochelper set session_key <key>`, or pass --session-key to `aochelper get`."
+                .to_string(),
+        )
+        .into());
+    }
+    first_cookie(
+        browser_sources(selection)?,
+        selection.browser.unwrap_or("any browser"),
+    )
+}
+
+/// Read the session cookie from the first of `sources` that has it. Fails with
+/// [`NoSessionKey`], listing why each source failed, if none of them has it.
+fn first_cookie<'a>(
+    sources: Vec<Box<dyn CookieSource + 'a>>,
+    searched: &str,
+) -> Result<(BrowserCookie, String)> {
+    let mut errors = Vec::new();
+    for source in sources {
+        match source.read_cookie() {
+            Ok((cookie, browser)) => {
+                tracing::debug!("Found cookie for advent of code from {browser}.");
+                return Ok((cookie, browser));
+            }
+            Err(err) => errors.push(format!("{err:#}")),
+        }
+    }
+    Err(NoSessionKey(format!(
+        "Could not read a session key from {searched}:\n  {}",
+        errors.join("\n  ")
+    ))
+    .into())
+}
+
+/// Error returned when no session key could be found in the browsers
+#[derive(Debug)]
+pub struct NoSessionKey(String);
+
+impl std::fmt::Display for NoSessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NoSessionKey {}
+
+/// The browser cookie stores to search, in order
+fn browser_sources<'a>(selection: BrowserSelection<'a>) -> Result<Vec<Box<dyn CookieSource + 'a>>> {
+    let BrowserSelection {
+        browser,
+        profile,
+        container,
+        ..
+    } = selection;
+    if let Some(browser) = browser {
+        validate_browser(browser)?;
+    }
+    let is_gecko = |id: &str| gecko::GECKO_BROWSERS.iter().any(|b| b.id == id);
+    let gecko_only = profile.is_some() || container.is_some();
+    if let Some(browser) = browser {
+        if gecko_only && !is_gecko(browser) {
+            return Err(anyhow::anyhow!(
+                "Profiles and containers can only be selected for Firefox-based browsers, not {browser}."
+            ));
+        }
+    }
+    let wanted = |id: &str| browser.unwrap_or(id) == id && (!gecko_only || is_gecko(id));
+
+    let mut sources: Vec<Box<dyn CookieSource>> = Vec::new();
+    if gecko::GECKO_BROWSERS.iter().any(|b| wanted(b.id)) {
+        sources.push(Box::new(gecko::GeckoSource {
+            browser,
+            profile,
+            container,
+        }));
+    }
+    if chromium::CHROMIUM_BROWSERS.iter().any(|b| wanted(b.id)) {
+        sources.push(Box::new(chromium::ChromiumSource { browser }));
+    }
+    // only report that Safari is unsupported if it was asked for
+    if wanted(SAFARI_ID) && (cfg!(target_os = "macos") || browser.is_some()) {
+        sources.push(Box::new(safari::SafariSource));
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// A cookie source that records being read, and has the cookie if it has
+    /// a value
+    struct FakeSource<'a> {
+        name: &'static str,
+        value: Option<&'static str>,
+        read: &'a RefCell<Vec<&'static str>>,
+    }
+
+    impl CookieSource for FakeSource<'_> {
+        fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+            self.read.borrow_mut().push(self.name);
+            let value = self
+                .value
+                .with_context(|| format!("No cookie in {}.", self.name))?;
+            let cookie = BrowserCookie {
+                value: value.to_string(),
+                expires: None,
+                created: None,
+            };
+            Ok((cookie, self.name.to_string()))
+        }
+    }
+
+    fn sources<'a>(
+        read: &'a RefCell<Vec<&'static str>>,
+        values: &[(&'static str, Option<&'static str>)],
+    ) -> Vec<Box<dyn CookieSource + 'a>> {
+        values
+            .iter()
+            .map(|&(name, value)| {
+                Box::new(FakeSource { name, value, read }) as Box<dyn CookieSource + 'a>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_source_with_the_cookie_wins() {
+        let read = RefCell::new(Vec::new());
+        let sources = sources(
+            &read,
+            &[
+                ("firefox", None),
+                ("chrome", Some("from-chrome")),
+                ("safari", Some("from-safari")),
+            ],
+        );
+        let (cookie, browser) = first_cookie(sources, "any browser").unwrap();
+        assert_eq!(cookie.value, "from-chrome");
+        assert_eq!(browser, "chrome");
+        assert_eq!(*read.borrow(), ["firefox", "chrome"]);
+    }
+
+    #[test]
+    fn no_source_with_the_cookie() {
+        let read = RefCell::new(Vec::new());
+        let sources = sources(&read, &[("firefox", None), ("chrome", None)]);
+        let err = first_cookie(sources, "any browser").err().unwrap();
+        assert!(err.is::<NoSessionKey>());
+        assert_eq!(
+            err.to_string(),
+            "Could not read a session key from any browser:\n  \
+             No cookie in firefox.\n  No cookie in chrome."
+        );
+        assert_eq!(*read.borrow(), ["firefox", "chrome"]);
+    }
+
+    #[test]
+    fn browser_cookies_disabled() {
+        let selection = BrowserSelection {
+            disabled: true,
+            ..Default::default()
+        };
+        let err = find_session_key(selection).err().unwrap();
+        assert!(err.is::<NoSessionKey>());
+    }
+
+    #[test]
+    fn every_kind_of_browser_is_searched() {
+        let selection = BrowserSelection::default();
+        let sources = browser_sources(selection).unwrap();
+        let expected = if cfg!(target_os = "macos") { 3 } else { 2 };
+        assert_eq!(sources.len(), expected);
+    }
+
+    #[test]
+    fn profiles_only_for_gecko_browsers() {
+        let selection = BrowserSelection {
+            browser: Some("chrome"),
+            profile: Some("work"),
+            ..Default::default()
+        };
+        assert!(browser_sources(selection).is_err());
+        let selection = BrowserSelection {
+            browser: Some("chrome"),
+            ..Default::default()
+        };
+        assert_eq!(browser_sources(selection).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unknown_browser() {
+        let selection = BrowserSelection {
+            browser: Some("netscape"),
+            ..Default::default()
+        };
+        let err = find_session_key(selection).err().unwrap();
+        assert!(err.to_string().starts_with("Unknown browser 'netscape'."));
+    }
+}
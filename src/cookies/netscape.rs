@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+
+use super::{cookie_host, cookie_name, BrowserCookie, CookieSource};
+
+/// Prefix curl and some browser extensions put on the domain of HttpOnly cookies
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// A Netscape cookies.txt file
+pub struct NetscapeSource<'a>(pub &'a Path);
+
+impl CookieSource for NetscapeSource<'_> {
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+        let name = format!("{:?}", self.0);
+        let cookie = read_netscape_host_cookie(self.0, cookie_host())?.check_expiry(&name)?;
+        Ok((cookie, name))
+    }
+}
+
+/// Read the session cookie for `hostname` from a Netscape cookies.txt file, as
+/// written by browser extensions and curl's cookie jar. Each line holds the
+/// tab-separated fields domain, subdomain flag, path, secure flag, expiry
+/// (seconds since the Unix epoch, 0 for session cookies), name and value.
+pub fn read_netscape_host_cookie(cookie_path: &Path, hostname: &str) -> Result<BrowserCookie> {
+    let contents = fs::read_to_string(cookie_path)
+        .with_context(|| format!("Failed to read {:?}", &cookie_path))?;
+    // curl writes the domain without the leading dot if the cookie was set
+    // without a Domain attribute
+    let bare_hostname = hostname.trim_start_matches('.');
+    for line in contents.lines() {
+        let line = line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line);
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [domain, _, _, _, expiry, name, value] = fields[..] else {
+            tracing::debug!("Skipping malformed line in {cookie_path:?}");
+            continue;
+        };
+        if domain.trim_start_matches('.') == bare_hostname && name == cookie_name() {
+            let expiry: i64 = expiry.trim().parse().unwrap_or(0);
+            return Ok(BrowserCookie {
+                value: value.trim_end().to_string(),
+                expires: match expiry {
+                    0 => None,
+                    _ => DateTime::from_timestamp(expiry, 0),
+                },
+                created: None,
+            });
+        }
+    }
+    Err(anyhow::anyhow!(
+        "No session cookie for '{hostname}' found in {:?}.",
+        &cookie_path
+    ))
+}
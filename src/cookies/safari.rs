@@ -0,0 +1,161 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use super::{cookie_host, cookie_name, BrowserCookie, CookieSource};
+
+/// Locations of Cookies.binarycookies relative to $HOME, newest Safari first
+const SAFARI_COOKIE_PATHS: &[&str] = &[
+    "Library/Containers/com.apple.Safari/Data/Library/Cookies/Cookies.binarycookies",
+    "Library/Cookies/Cookies.binarycookies",
+];
+
+/// Safari, on macOS only
+pub struct SafariSource;
+
+impl CookieSource for SafariSource {
+    fn read_cookie(&self) -> Result<(BrowserCookie, String)> {
+        if !cfg!(target_os = "macos") {
+            return Err(anyhow::anyhow!("Safari is only supported on macOS."));
+        }
+        let cookie_path = find_safari_cookie()?;
+        tracing::debug!("Found Safari cookies at {cookie_path:?}");
+        let cookie = read_safari_host_cookie(&cookie_path, cookie_host())
+            .and_then(|cookie| cookie.check_expiry("Safari"))
+            .with_context(|| format!("Failed to read Safari cookies from {:?}", &cookie_path))?;
+        Ok((cookie, "Safari".to_string()))
+    }
+}
+
+/// Find Safari's Cookies.binarycookies file.
+pub fn find_safari_cookie() -> Result<PathBuf> {
+    let home = env::var_os("HOME").context("Could not find Safari cookies. $HOME is not set.")?;
+    SAFARI_COOKIE_PATHS
+        .iter()
+        .map(|path| Path::new(&home).join(path))
+        .find(|path| path.exists())
+        .with_context(|| {
+            format!("Could not find Safari cookies in {home:?}. The terminal may need Full Disk Access to read them.")
+        })
+}
+
+/// Seconds between the Unix epoch and the Mac epoch, 2001-01-01
+const MAC_EPOCH_OFFSET: f64 = 978_307_200.0;
+
+/// A cookie record from a binarycookies file
+struct SafariCookie {
+    domain: String,
+    name: String,
+    value: String,
+    expires: Option<DateTime<Utc>>,
+    created: Option<DateTime<Utc>>,
+}
+
+fn read_u32_be(buf: &[u8], offset: usize) -> Result<u32> {
+    buf.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        .context("Unexpected end of Safari cookie file.")
+}
+
+fn read_u32_le(buf: &[u8], offset: usize) -> Result<u32> {
+    buf.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .context("Unexpected end of Safari cookie file.")
+}
+
+/// Read a date at `offset` of a cookie record, stored as a little-endian
+/// double of seconds since the Mac epoch
+fn read_mac_time(record: &[u8], offset: usize) -> Result<Option<DateTime<Utc>>> {
+    let seconds = record
+        .get(offset..offset + 8)
+        .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+        .context("Unexpected end of Safari cookie record.")?;
+    Ok(DateTime::from_timestamp(
+        (seconds + MAC_EPOCH_OFFSET) as i64,
+        0,
+    ))
+}
+
+/// Read the null-terminated string at `offset` of a cookie record
+fn read_cstr(record: &[u8], offset: u32) -> Result<String> {
+    let tail = record
+        .get(offset as usize..)
+        .context("Unexpected end of Safari cookie record.")?;
+    let end = tail
+        .iter()
+        .position(|&b| b == 0)
+        .context("Unterminated string in Safari cookie record.")?;
+    Ok(String::from_utf8_lossy(&tail[..end]).into_owned())
+}
+
+/// Parse a single cookie record. Offsets of the strings are stored as
+/// little-endian integers relative to the start of the record.
+fn parse_cookie(record: &[u8]) -> Result<SafariCookie> {
+    Ok(SafariCookie {
+        domain: read_cstr(record, read_u32_le(record, 16)?)?,
+        name: read_cstr(record, read_u32_le(record, 20)?)?,
+        value: read_cstr(record, read_u32_le(record, 28)?)?,
+        expires: read_mac_time(record, 40)?,
+        created: read_mac_time(record, 48)?,
+    })
+}
+
+/// Parse a page of cookies. Each page starts with the tag 0x00000100, followed
+/// by the number of cookies and the offset of each cookie within the page.
+fn parse_page(page: &[u8]) -> Result<Vec<SafariCookie>> {
+    if read_u32_be(page, 0)? != 0x0000_0100 {
+        return Err(anyhow::anyhow!(
+            "Invalid page header in Safari cookie file."
+        ));
+    }
+    let num_cookies = read_u32_le(page, 4)? as usize;
+    (0..num_cookies)
+        .map(|i| {
+            let offset = read_u32_le(page, 8 + 4 * i)? as usize;
+            let size = read_u32_le(page, offset)? as usize;
+            let record = page
+                .get(offset..offset + size)
+                .context("Unexpected end of Safari cookie page.")?;
+            parse_cookie(record)
+        })
+        .collect()
+}
+
+/// Parse a binarycookies file: the magic `cook`, the big-endian number of pages
+/// and the size of each page, followed by the pages themselves.
+fn parse_binarycookies(buf: &[u8]) -> Result<Vec<SafariCookie>> {
+    if buf.get(..4) != Some(b"cook") {
+        return Err(anyhow::anyhow!("Not a Safari binarycookies file."));
+    }
+    let num_pages = read_u32_be(buf, 4)? as usize;
+    let mut page_start = 8 + 4 * num_pages;
+    let mut cookies = Vec::new();
+    for i in 0..num_pages {
+        let page_size = read_u32_be(buf, 8 + 4 * i)? as usize;
+        let page = buf
+            .get(page_start..page_start + page_size)
+            .context("Unexpected end of Safari cookie file.")?;
+        cookies.extend(parse_page(page)?);
+        page_start += page_size;
+    }
+    Ok(cookies)
+}
+
+pub fn read_safari_host_cookie(cookie_path: &Path, hostname: &str) -> Result<BrowserCookie> {
+    let buf =
+        fs::read(cookie_path).with_context(|| format!("Failed to read {:?}", &cookie_path))?;
+    parse_binarycookies(&buf)?
+        .into_iter()
+        .find(|cookie| cookie.domain == hostname && cookie.name == cookie_name())
+        .map(|cookie| BrowserCookie {
+            value: cookie.value,
+            expires: cookie.expires,
+            created: cookie.created,
+        })
+        .with_context(|| {
+            format!("No cookie found for '{hostname}'. You may need to log in via the web browswer first.")
+        })
+}
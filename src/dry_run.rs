@@ -0,0 +1,41 @@
+//! Dry runs, with --dry-run: commands work out what they would request and
+//! write, and say so, without sending anything to the server or changing any
+//! files.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::report::say;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+pub fn set(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Say what would be done
+pub fn would(what: fmt::Arguments) {
+    say!("Would {what}");
+}
+
+/// Returned where a dry run stops, before a request whose answer the rest of
+/// the command depends on. It is not a failure.
+#[derive(Debug)]
+pub struct Stopped;
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Stopped before sending a request, as this is a dry run")
+    }
+}
+
+impl std::error::Error for Stopped {}
+
+/// Whether `err` only means that a dry run stopped
+pub fn stopped(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<Stopped>())
+}
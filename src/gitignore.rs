@@ -0,0 +1,156 @@
+//! Keeping inputs out of git: Advent of Code asks that inputs aren't shared,
+//! so when they are saved inside a git repository, their directory belongs in
+//! its .gitignore.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+
+use crate::{dry_run, prompt};
+
+const GITIGNORE_FILE: &str = ".gitignore";
+
+/// Name of a file like the inputs, to ask git whether they are ignored
+const SAMPLE_INPUT: &str = "2015.01";
+
+/// Absolute path of `dir`, which needn't exist yet
+fn absolute(dir: &Path) -> io::Result<PathBuf> {
+    match fs::canonicalize(dir) {
+        Ok(dir) => Ok(dir),
+        Err(err) => match (dir.parent(), dir.file_name()) {
+            (Some(parent), Some(name)) => {
+                let parent = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+                Ok(absolute(parent)?.join(name))
+            }
+            _ => Err(err),
+        },
+    }
+}
+
+/// The root of the git repository `dir` is in, if any
+fn repo_root(dir: &Path) -> Option<PathBuf> {
+    let dir = absolute(dir).ok()?;
+    dir.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// What keeps the inputs in `dir` out of the repository at `root`: the whole
+/// directory, or if the inputs are at the root, the files named like them
+fn pattern(root: &Path, dir: &Path) -> Result<String> {
+    let dir = absolute(dir)?;
+    let relative = dir.strip_prefix(root)?;
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if components.is_empty() {
+        return Ok("/[0-9][0-9][0-9][0-9].[0-9][0-9]".to_string());
+    }
+    Ok(format!("/{}/", components.join("/")))
+}
+
+/// Whether the inputs in `dir` are ignored already. git is asked if it is
+/// installed, and otherwise .gitignore is searched for the pattern.
+fn is_ignored(root: &Path, dir: &Path, pattern: &str) -> Result<bool> {
+    let sample = absolute(dir)?.join(SAMPLE_INPUT);
+    let checked = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["check-ignore", "--quiet", "--no-index"])
+        .arg(&sample)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match checked.ok().and_then(|status| status.code()) {
+        Some(0) => return Ok(true),
+        Some(1) => return Ok(false),
+        _ => (),
+    }
+    let bare = pattern.trim_start_matches('/').trim_end_matches('/');
+    Ok(gitignore_lines(root)?
+        .iter()
+        .map(|line| line.trim().trim_start_matches('/').trim_end_matches('/'))
+        .any(|line| line == bare))
+}
+
+fn gitignore_lines(root: &Path) -> Result<Vec<String>> {
+    let path = root.join(GITIGNORE_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(contents.lines().map(str::to_string).collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {:?}", path)),
+    }
+}
+
+/// Append `pattern` to the .gitignore at `root`
+fn append(root: &Path, pattern: &str) -> Result<()> {
+    let path = root.join(GITIGNORE_FILE);
+    if dry_run::enabled() {
+        dry_run::would(format_args!("add {pattern} to {}", path.display()));
+        return Ok(());
+    }
+    let mut contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!(
+        "# Advent of Code inputs, which aren't to be shared\n{pattern}\n"
+    ));
+    fs::write(&path, contents).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// What [`ignore_inputs`] found or did
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    NotInRepository,
+    AlreadyIgnored {
+        gitignore: PathBuf,
+    },
+    Added {
+        gitignore: PathBuf,
+        pattern: String,
+    },
+    /// The user chose not to add it, or couldn't be asked
+    NotAdded {
+        gitignore: PathBuf,
+        pattern: String,
+    },
+}
+
+/// Make sure the inputs in `dir` are ignored by git, if it is in a git
+/// repository. Without `add`, the user is asked first, except in a dry run.
+pub fn ignore_inputs(dir: &Path, add: bool) -> Result<Outcome> {
+    let Some(root) = repo_root(dir) else {
+        return Ok(Outcome::NotInRepository);
+    };
+    let gitignore = root.join(GITIGNORE_FILE);
+    let pattern = pattern(&root, dir)?;
+    if is_ignored(&root, dir, &pattern)? {
+        return Ok(Outcome::AlreadyIgnored { gitignore });
+    }
+    let question = format!(
+        "{} is in a git repository. Add {pattern} to {} so that inputs aren't committed?",
+        dir.display(),
+        gitignore.display()
+    );
+    let asked = || -> Result<bool> {
+        Ok(prompt::interactive() && !dry_run::enabled() && prompt::confirm(&question, true)?)
+    };
+    if !add && !asked()? {
+        return Ok(Outcome::NotAdded { gitignore, pattern });
+    }
+    append(&root, &pattern)?;
+    Ok(Outcome::Added { gitignore, pattern })
+}
@@ -0,0 +1,567 @@
+//! History of submitted answers and downloaded inputs, kept in a SQLite
+//! database in the data directory
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::config::data_dir;
+use crate::submit::Verdict;
+
+const HISTORY_DB: &str = "history.sqlite";
+
+/// A puzzle part, as answered by one account
+pub struct Puzzle<'a> {
+    pub account: Option<&'a str>,
+    pub year: u16,
+    pub day: u8,
+    pub part: u8,
+}
+
+impl Puzzle<'_> {
+    fn account(&self) -> &str {
+        self.account.unwrap_or("default")
+    }
+}
+
+/// A previously submitted answer
+pub struct Guess {
+    pub answer: String,
+    pub verdict: Verdict,
+    pub submitted: DateTime<Utc>,
+}
+
+/// Range the answer must be in, going by earlier "too high" and "too low"
+/// verdicts: above the highest answer that was too low, and below the lowest
+/// one that was too high
+#[derive(Debug, Default, Serialize)]
+pub struct Bounds {
+    pub too_low: Option<i128>,
+    pub too_high: Option<i128>,
+}
+
+impl Bounds {
+    /// Explain why `answer` can't be right, if it is outside the bounds
+    pub fn check(&self, answer: i128) -> Option<String> {
+        match (self.too_low, self.too_high) {
+            (Some(low), _) if answer <= low => Some(format!(
+                "You were told {low} is too low; {answer} can't be right."
+            )),
+            (_, Some(high)) if answer >= high => Some(format!(
+                "You were told {high} is too high; {answer} can't be right."
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Bounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.too_low, self.too_high) {
+            (Some(low), Some(high)) => write!(f, "The answer is between {low} and {high}."),
+            (Some(low), None) => write!(f, "The answer is greater than {low}."),
+            (None, Some(high)) => write!(f, "The answer is less than {high}."),
+            (None, None) => write!(f, "No bounds on the answer are known yet."),
+        }
+    }
+}
+
+/// Something that happened to a puzzle, as `aochelper history` lists it
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub time: DateTime<Utc>,
+    pub year: u16,
+    pub day: u8,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The input was downloaded, or copied from the cache. The path is `None`
+    /// for an input that was only written to stdout.
+    Input {
+        cache_hit: bool,
+        path: Option<String>,
+    },
+    /// An answer was submitted; the verdict is the name it is stored under
+    Answer {
+        part: u8,
+        answer: String,
+        verdict: String,
+    },
+}
+
+/// A submitted answer as `archive` saves it, of any account
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AnswerRecord {
+    pub account: String,
+    pub year: u16,
+    pub day: u8,
+    pub part: u8,
+    pub answer: String,
+    pub verdict: String,
+    pub submitted: String,
+}
+
+/// How far the answers to a puzzle part got
+#[derive(Debug, Default, Serialize)]
+pub struct Progress {
+    pub solved: bool,
+    /// Number of wrong answers given
+    pub wrong: usize,
+}
+
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open the history database, creating it if needed. Without a data
+    /// directory, history is only kept for this run.
+    pub fn open() -> Result<History> {
+        let conn = match data_dir() {
+            Some(dir) => {
+                fs::create_dir_all(&dir)?;
+                let db_path = dir.join(HISTORY_DB);
+                Connection::open(&db_path)
+                    .with_context(|| format!("Failed to open {:?}", &db_path))?
+            }
+            None => Connection::open_in_memory()?,
+        };
+        History::with_connection(conn)
+    }
+
+    /// History kept in `conn`, with the tables set up
+    fn with_connection(conn: Connection) -> Result<History> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS guesses (
+                account TEXT NOT NULL,
+                year INTEGER NOT NULL,
+                day INTEGER NOT NULL,
+                part INTEGER NOT NULL,
+                answer TEXT NOT NULL,
+                verdict TEXT NOT NULL,
+                submitted TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS guesses_puzzle ON guesses (account, year, day, part);
+            CREATE TABLE IF NOT EXISTS inputs (
+                account TEXT NOT NULL,
+                year INTEGER NOT NULL,
+                day INTEGER NOT NULL,
+                cache_hit INTEGER NOT NULL,
+                path TEXT,
+                fetched TEXT NOT NULL
+            );",
+        )
+        .context("Failed to set up the history database")?;
+        Ok(History { conn })
+    }
+
+    pub fn record(&self, puzzle: &Puzzle, answer: &str, verdict: &Verdict) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO guesses (account, year, day, part, answer, verdict, submitted)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                puzzle.account(),
+                puzzle.year,
+                puzzle.day,
+                puzzle.part,
+                answer,
+                verdict.id(),
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Record that the input of a day was downloaded, or copied from the cache
+    pub fn record_input(
+        &self,
+        account: Option<&str>,
+        year: u16,
+        day: u8,
+        cache_hit: bool,
+        path: Option<&Path>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO inputs (account, year, day, cache_hit, path, fetched)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                account.unwrap_or("default"),
+                year,
+                day,
+                cache_hit,
+                path.map(|path| path.to_string_lossy()),
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Inputs fetched and answers submitted, oldest first, of one year and
+    /// one day if given
+    pub fn events(
+        &self,
+        account: Option<&str>,
+        year: Option<u16>,
+        day: Option<u8>,
+    ) -> Result<Vec<Event>> {
+        let account = account.unwrap_or("default");
+        let time = |text: String| {
+            DateTime::parse_from_rfc3339(&text)
+                .map(|time| time.with_timezone(&Utc))
+                .unwrap_or_default()
+        };
+        let mut events = Vec::new();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT year, day, cache_hit, path, fetched FROM inputs
+             WHERE account = ?1 AND (?2 IS NULL OR year = ?2) AND (?3 IS NULL OR day = ?3)",
+        )?;
+        let rows = stmt.query_map(params![account, year, day], |row| {
+            Ok(Event {
+                year: row.get(0)?,
+                day: row.get(1)?,
+                kind: EventKind::Input {
+                    cache_hit: row.get(2)?,
+                    path: row.get(3)?,
+                },
+                time: time(row.get(4)?),
+            })
+        })?;
+        for row in rows {
+            events.push(row?);
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT year, day, part, answer, verdict, submitted FROM guesses
+             WHERE account = ?1 AND (?2 IS NULL OR year = ?2) AND (?3 IS NULL OR day = ?3)",
+        )?;
+        let rows = stmt.query_map(params![account, year, day], |row| {
+            Ok(Event {
+                year: row.get(0)?,
+                day: row.get(1)?,
+                kind: EventKind::Answer {
+                    part: row.get(2)?,
+                    answer: row.get(3)?,
+                    verdict: row.get(4)?,
+                },
+                time: time(row.get(5)?),
+            })
+        })?;
+        for row in rows {
+            events.push(row?);
+        }
+
+        events.sort_by_key(|event| event.time);
+        Ok(events)
+    }
+
+    /// Answers submitted by every account, of one year if given
+    pub fn answers(&self, year: Option<u16>) -> Result<Vec<AnswerRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT account, year, day, part, answer, verdict, submitted FROM guesses
+             WHERE ?1 IS NULL OR year = ?1 ORDER BY submitted",
+        )?;
+        let rows = stmt.query_map(params![year], |row| {
+            Ok(AnswerRecord {
+                account: row.get(0)?,
+                year: row.get(1)?,
+                day: row.get(2)?,
+                part: row.get(3)?,
+                answer: row.get(4)?,
+                verdict: row.get(5)?,
+                submitted: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Add answers from an archive, leaving out the ones that are already in
+    /// the history. Returns how many were added.
+    pub fn import_answers(&mut self, answers: &[AnswerRecord]) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut added = 0;
+        for answer in answers {
+            added += tx.execute(
+                "INSERT INTO guesses (account, year, day, part, answer, verdict, submitted)
+                 SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM guesses
+                     WHERE account = ?1 AND year = ?2 AND day = ?3 AND part = ?4
+                       AND answer = ?5 AND submitted = ?7
+                 )",
+                params![
+                    answer.account,
+                    answer.year,
+                    answer.day,
+                    answer.part,
+                    answer.answer,
+                    answer.verdict,
+                    answer.submitted,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// The latest verdict that said `answer` is wrong, if any
+    pub fn wrong_guess(&self, puzzle: &Puzzle, answer: &str) -> Result<Option<Guess>> {
+        let guess = self
+            .conn
+            .query_row(
+                "SELECT answer, verdict, submitted FROM guesses
+                 WHERE account = ?1 AND year = ?2 AND day = ?3 AND part = ?4 AND answer = ?5
+                   AND verdict IN ('incorrect', 'too_high', 'too_low')
+                 ORDER BY submitted DESC LIMIT 1",
+                params![
+                    puzzle.account(),
+                    puzzle.year,
+                    puzzle.day,
+                    puzzle.part,
+                    answer
+                ],
+                row_to_guess,
+            )
+            .optional()?;
+        Ok(guess)
+    }
+
+    /// Bounds on the answer from the numeric answers that were too high or too low
+    pub fn bounds(&self, puzzle: &Puzzle) -> Result<Bounds> {
+        let mut stmt = self.conn.prepare(
+            "SELECT answer, verdict FROM guesses
+             WHERE account = ?1 AND year = ?2 AND day = ?3 AND part = ?4
+               AND verdict IN ('too_high', 'too_low')",
+        )?;
+        let rows = stmt.query_map(
+            params![puzzle.account(), puzzle.year, puzzle.day, puzzle.part],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        let mut bounds = Bounds::default();
+        for row in rows {
+            let (answer, verdict) = row?;
+            let Ok(answer) = answer.parse::<i128>() else {
+                continue;
+            };
+            match Verdict::from_id(&verdict) {
+                Verdict::TooLow => bounds.too_low = bounds.too_low.max(Some(answer)),
+                Verdict::TooHigh => {
+                    bounds.too_high = Some(bounds.too_high.map_or(answer, |high| high.min(answer)))
+                }
+                _ => (),
+            }
+        }
+        Ok(bounds)
+    }
+
+    /// Progress on each part of the puzzles of a year, by day and part, for
+    /// the parts that were answered
+    pub fn progress(
+        &self,
+        account: Option<&str>,
+        year: u16,
+    ) -> Result<BTreeMap<(u8, u8), Progress>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT day, part, verdict FROM guesses WHERE account = ?1 AND year = ?2")?;
+        let rows = stmt.query_map(params![account.unwrap_or("default"), year], |row| {
+            Ok((
+                row.get::<_, u8>(0)?,
+                row.get::<_, u8>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut progress: BTreeMap<(u8, u8), Progress> = BTreeMap::new();
+        for row in rows {
+            let (day, part, verdict) = row?;
+            let part = progress.entry((day, part)).or_default();
+            match Verdict::from_id(&verdict) {
+                Verdict::Correct | Verdict::AlreadySolved => part.solved = true,
+                Verdict::Incorrect | Verdict::TooHigh | Verdict::TooLow => part.wrong += 1,
+                _ => (),
+            }
+        }
+        Ok(progress)
+    }
+}
+
+fn row_to_guess(row: &rusqlite::Row) -> rusqlite::Result<Guess> {
+    let verdict: String = row.get(1)?;
+    let submitted: String = row.get(2)?;
+    Ok(Guess {
+        answer: row.get(0)?,
+        verdict: Verdict::from_id(&verdict),
+        submitted: DateTime::parse_from_rfc3339(&submitted)
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn puzzle(account: Option<&str>, year: u16, day: u8, part: u8) -> Puzzle<'_> {
+        Puzzle {
+            account,
+            year,
+            day,
+            part,
+        }
+    }
+
+    #[test]
+    fn wrong_guesses_are_found() {
+        let history = history();
+        let part1 = puzzle(None, 2022, 1, 1);
+        history.record(&part1, "10", &Verdict::Incorrect).unwrap();
+        history.record(&part1, "20", &Verdict::TooHigh).unwrap();
+        history.record(&part1, "5", &Verdict::TooLow).unwrap();
+        for (answer, verdict) in [
+            ("10", Verdict::Incorrect),
+            ("20", Verdict::TooHigh),
+            ("5", Verdict::TooLow),
+        ] {
+            let guess = history.wrong_guess(&part1, answer).unwrap().unwrap();
+            assert_eq!(guess.answer, answer);
+            assert_eq!(guess.verdict, verdict);
+        }
+        assert!(history.wrong_guess(&part1, "15").unwrap().is_none());
+    }
+
+    #[test]
+    fn other_verdicts_are_not_wrong_guesses() {
+        let history = history();
+        let part1 = puzzle(None, 2022, 1, 1);
+        history.record(&part1, "1", &Verdict::Correct).unwrap();
+        history
+            .record(&part1, "2", &Verdict::RateLimited { wait: None })
+            .unwrap();
+        history
+            .record(&part1, "3", &Verdict::AlreadySolved)
+            .unwrap();
+        history.record(&part1, "4", &Verdict::WrongLevel).unwrap();
+        history.record(&part1, "5", &Verdict::Unknown).unwrap();
+        for answer in ["1", "2", "3", "4", "5"] {
+            assert!(history.wrong_guess(&part1, answer).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn wrong_guesses_are_kept_to_their_puzzle() {
+        let history = history();
+        history
+            .record(&puzzle(None, 2022, 1, 1), "10", &Verdict::Incorrect)
+            .unwrap();
+        for other in [
+            puzzle(Some("work"), 2022, 1, 1),
+            puzzle(None, 2021, 1, 1),
+            puzzle(None, 2022, 2, 1),
+            puzzle(None, 2022, 1, 2),
+        ] {
+            assert!(history.wrong_guess(&other, "10").unwrap().is_none());
+        }
+        // No account is the default account
+        assert!(history
+            .wrong_guess(&puzzle(Some("default"), 2022, 1, 1), "10")
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn answers_on_or_outside_the_bounds_are_ruled_out() {
+        let bounds = Bounds {
+            too_low: Some(10),
+            too_high: Some(20),
+        };
+        assert!(bounds.check(9).is_some());
+        assert!(bounds.check(10).is_some());
+        assert!(bounds.check(11).is_none());
+        assert!(bounds.check(19).is_none());
+        assert!(bounds.check(20).is_some());
+        assert!(bounds.check(21).is_some());
+        assert!(Bounds::default().check(i128::MIN).is_none());
+    }
+
+    #[test]
+    fn one_sided_bounds() {
+        let low = Bounds {
+            too_low: Some(10),
+            too_high: None,
+        };
+        assert!(low.check(10).is_some());
+        assert!(low.check(i128::MAX).is_none());
+        let high = Bounds {
+            too_low: None,
+            too_high: Some(20),
+        };
+        assert!(high.check(20).is_some());
+        assert!(high.check(i128::MIN).is_none());
+    }
+
+    #[test]
+    fn bounds_are_the_closest_guesses() {
+        let history = history();
+        let part1 = puzzle(None, 2022, 1, 1);
+        for (answer, verdict) in [
+            ("5", Verdict::TooLow),
+            ("12", Verdict::TooLow),
+            ("8", Verdict::TooLow),
+            ("30", Verdict::TooHigh),
+            ("25", Verdict::TooHigh),
+            ("40", Verdict::TooHigh),
+            // Not numbers, so they say nothing about the bounds
+            ("abc", Verdict::TooLow),
+            ("1e3", Verdict::TooHigh),
+            ("15", Verdict::Incorrect),
+        ] {
+            history.record(&part1, answer, &verdict).unwrap();
+        }
+        // Other puzzles don't count
+        history
+            .record(&puzzle(None, 2022, 1, 2), "20", &Verdict::TooLow)
+            .unwrap();
+        history
+            .record(&puzzle(Some("work"), 2022, 1, 1), "22", &Verdict::TooHigh)
+            .unwrap();
+
+        let bounds = history.bounds(&part1).unwrap();
+        assert_eq!(bounds.too_low, Some(12));
+        assert_eq!(bounds.too_high, Some(25));
+        assert!(history
+            .bounds(&puzzle(None, 2022, 2, 1))
+            .unwrap()
+            .too_low
+            .is_none());
+    }
+
+    #[test]
+    fn negative_and_large_answers_bound() {
+        let history = history();
+        let part1 = puzzle(None, 2022, 1, 1);
+        history.record(&part1, "-7", &Verdict::TooLow).unwrap();
+        history
+            .record(
+                &part1,
+                "170141183460469231731687303715884105727",
+                &Verdict::TooHigh,
+            )
+            .unwrap();
+        let bounds = history.bounds(&part1).unwrap();
+        assert_eq!(bounds.too_low, Some(-7));
+        assert_eq!(bounds.too_high, Some(i128::MAX));
+    }
+}
@@ -0,0 +1,285 @@
+//! Hooks: shell commands from the `[hooks]` table of aochelper.toml, run
+//! before and after some commands so that users can wire in their own
+//! automation, such as running the tests before submitting or committing once
+//! an answer is right.
+//!
+//! `{year}`, `{day}`, `{part}`, `{answer}`, `{verdict}`, `{input}` and
+//! `{account}` in a command are replaced with their values, quoted for the
+//! shell, and the same values are in `AOC_YEAR`, `AOC_DAY` and so on.
+
+use std::fmt;
+use std::io;
+use std::process::{Command, ExitStatus, Stdio};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{color, dry_run, suggest};
+
+/// When a hook runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    /// Before an answer is submitted. If it fails, the answer isn't.
+    PreSubmit,
+    /// After `get` or `cat` downloaded an input or copied it from the cache
+    PostGet,
+    /// After an answer was submitted, whatever the verdict
+    PostSubmit,
+    /// After an answer was right
+    PostSubmitCorrect,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 4] = [
+        Hook::PreSubmit,
+        Hook::PostGet,
+        Hook::PostSubmit,
+        Hook::PostSubmitCorrect,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Hook::PreSubmit => "pre_submit",
+            Hook::PostGet => "post_get",
+            Hook::PostSubmit => "post_submit",
+            Hook::PostSubmitCorrect => "post_submit_correct",
+        }
+    }
+
+    /// Key to give `aochelper set` to set the hook
+    pub fn key(&self) -> &'static str {
+        match self {
+            Hook::PreSubmit => "hooks.pre_submit",
+            Hook::PostGet => "hooks.post_get",
+            Hook::PostSubmit => "hooks.post_submit",
+            Hook::PostSubmitCorrect => "hooks.post_submit_correct",
+        }
+    }
+
+    pub fn parse(id: &str) -> Result<Hook> {
+        let ids = Hook::ALL.map(|hook| hook.id());
+        Hook::ALL
+            .into_iter()
+            .find(|hook| hook.id() == id)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown hook '{id}'.{} Hooks are: {}",
+                    suggest::did_you_mean(id, &ids),
+                    ids.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// The `[hooks]` table of aochelper.toml
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Hooks {
+    pub pre_submit: Option<String>,
+    pub post_get: Option<String>,
+    pub post_submit: Option<String>,
+    pub post_submit_correct: Option<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        Hook::ALL.iter().all(|hook| self.command(*hook).is_none())
+    }
+
+    pub fn command(&self, hook: Hook) -> Option<&str> {
+        match hook {
+            Hook::PreSubmit => &self.pre_submit,
+            Hook::PostGet => &self.post_get,
+            Hook::PostSubmit => &self.post_submit,
+            Hook::PostSubmitCorrect => &self.post_submit_correct,
+        }
+        .as_deref()
+    }
+
+    /// Set the command of a hook, or remove it with an empty one
+    pub fn set(&mut self, hook: Hook, command: &str) {
+        let slot = match hook {
+            Hook::PreSubmit => &mut self.pre_submit,
+            Hook::PostGet => &mut self.post_get,
+            Hook::PostSubmit => &mut self.post_submit,
+            Hook::PostSubmitCorrect => &mut self.post_submit_correct,
+        };
+        *slot = Some(command.to_string()).filter(|command| !command.trim().is_empty());
+    }
+}
+
+/// Error returned when a hook fails
+#[derive(Debug)]
+pub struct HookFailed {
+    pub hook: Hook,
+    pub status: ExitStatus,
+}
+
+impl fmt::Display for HookFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The {} hook failed: {}", self.hook, self.status)
+    }
+}
+
+impl std::error::Error for HookFailed {}
+
+/// Quote a value for a POSIX shell, leaving it as it is when that is safe
+pub fn shell_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:,=@%".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Quote a value for cmd.exe
+#[cfg(windows)]
+pub fn quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || r"/\._-+:,=@".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+}
+
+/// Quote a value for the shell hooks run in
+#[cfg(not(windows))]
+pub fn quote(value: &str) -> String {
+    shell_quote(value)
+}
+
+/// Replace the `{name}` placeholders in a hook's command, in one pass from
+/// left to right, so that a value holding something like `{input}` is left as
+/// it is. Other braces are kept.
+fn expand(command: &str, vars: &[(&str, String)]) -> String {
+    let mut expanded = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let (_, value) = vars.iter().find(|(name, _)| *name == &rest[1..end])?;
+            Some((end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                expanded.push_str(&quote(value));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                expanded.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Command running `command` in the shell: cmd.exe on Windows and sh elsewhere
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    // As it is, since cmd.exe doesn't unquote its arguments the usual way
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").raw_arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+/// Run a hook, if it is set, with `vars` such as `("day", "5")`. Its output
+/// goes to stderr, as stdout may be for an input or the JSON result.
+pub fn run(hooks: &Hooks, hook: Hook, vars: &[(&str, String)]) -> Result<()> {
+    let Some(command) = hooks.command(hook) else {
+        return Ok(());
+    };
+    let command = expand(command, vars);
+    if dry_run::enabled() {
+        dry_run::would(format_args!("run the {hook} hook: {command}"));
+        return Ok(());
+    }
+    tracing::info!("Running the {hook} hook: {command}");
+    let status = shell(&command)
+        .envs(
+            vars.iter()
+                .map(|(name, value)| (format!("AOC_{}", name.to_uppercase()), value)),
+        )
+        .stdout(Stdio::from(io::stderr()))
+        .status()
+        .with_context(|| format!("Failed to run the {hook} hook"))?;
+    if !status.success() {
+        return Err(HookFailed { hook, status }.into());
+    }
+    Ok(())
+}
+
+/// Run a hook after something was done, when failing is only worth a warning
+pub fn run_after(hooks: &Hooks, hook: Hook, vars: &[(&str, String)]) {
+    if let Err(err) = run(hooks, hook, vars) {
+        eprintln!("{} {err:#}", color::err("Warning:", color::WARNING));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_values_are_not_quoted() {
+        assert_eq!(shell_quote("inputs/2022.01"), "inputs/2022.01");
+        assert_eq!(shell_quote("a-b_c+d:e,f=g@h%i"), "a-b_c+d:e,f=g@h%i");
+    }
+
+    #[test]
+    fn unsafe_values_are_quoted() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("my inputs"), "'my inputs'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("a;rm -rf b"), "'a;rm -rf b'");
+    }
+
+    #[test]
+    fn single_quotes_are_escaped() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn placeholders_are_expanded() {
+        let vars = [
+            ("day", "5".to_string()),
+            ("input", "inputs/2022.05".to_string()),
+        ];
+        assert_eq!(
+            expand("cat {input} # {day}", &vars),
+            "cat inputs/2022.05 # 5"
+        );
+        assert_eq!(expand("echo {unknown} {day", &vars), "echo {unknown} {day");
+        assert_eq!(expand("{{day}}", &vars), "{5}");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn values_are_not_expanded_again() {
+        let vars = [
+            ("answer", "{input}".to_string()),
+            ("input", "inputs/2022.05".to_string()),
+        ];
+        assert_eq!(
+            expand("echo {answer} {input}", &vars),
+            "echo '{input}' inputs/2022.05"
+        );
+    }
+}
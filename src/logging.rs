@@ -0,0 +1,297 @@
+//! Logging that keeps session keys out of the terminal. Every session key the
+//! program handles is registered here, and masked in log output and in the
+//! error printed on exit. Logs can also go to a file, for runs nobody watches.
+//!
+//! Logging goes through `tracing`, with spans around finding the session key,
+//! requests and writing files, so that a message shows what it happened
+//! during. Messages of libraries that use `log` end up there as well.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, Layer, Registry};
+
+use crate::color;
+use crate::session::redact;
+
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Mask `secret` wherever it shows up in log output from now on
+pub fn register_secret(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap_or_else(|err| err.into_inner());
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Replace every registered secret in `text` with its redacted form
+pub fn redact_secrets(text: &str) -> String {
+    let secrets = SECRETS.lock().unwrap_or_else(|err| err.into_inner());
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret, &redact(secret))
+    })
+}
+
+/// Size past which the log file is moved to `<file>.1` and a new one started
+const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Log file given with --log-file, which takes precedence over aochelper.toml
+static LOG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+/// Log file from aochelper.toml
+static LOG_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+/// The log file being written to, opened on the first message for it
+static OPEN_LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+pub fn set_log_file_override(path: Option<PathBuf>) {
+    *LOG_FILE_OVERRIDE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = path;
+}
+
+pub fn set_log_file(path: Option<PathBuf>) {
+    *LOG_FILE.lock().unwrap_or_else(|err| err.into_inner()) = path;
+}
+
+fn log_file_path() -> Option<PathBuf> {
+    let path = LOG_FILE_OVERRIDE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    path.or_else(|| {
+        LOG_FILE
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    })
+}
+
+/// How log messages are written
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// A line of text for each message
+    #[default]
+    Text,
+    /// A JSON object for each message, with the fields of its spans
+    Json,
+}
+
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
+
+struct LogFile {
+    path: PathBuf,
+    /// None if the file couldn't be opened, which has been reported
+    file: Option<File>,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path) -> LogFile {
+        let mut log_file = LogFile {
+            path: path.to_path_buf(),
+            file: None,
+            size: 0,
+        };
+        if let Err(err) = log_file.reopen() {
+            eprintln!(
+                "{} Can't write logs to {}: {err}",
+                color::err("Warning:", color::WARNING),
+                path.display()
+            );
+        }
+        log_file
+    }
+
+    /// Open the file for appending, after rotating it if it's too big, and
+    /// note the start of this run
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = None;
+        if let Some(parent) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let size = fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        if size > MAX_LOG_FILE_SIZE {
+            let mut old = self.path.clone().into_os_string();
+            old.push(".1");
+            fs::rename(&self.path, old)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        let args: Vec<String> = std::env::args().collect();
+        let started = format!(
+            "aochelper {} started: {}",
+            env!("CARGO_PKG_VERSION"),
+            redact_secrets(&args.join(" "))
+        );
+        self.write(note_line("INFO", &started).as_bytes());
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        if file.write_all(bytes).is_ok() {
+            self.size += bytes.len() as u64;
+        }
+        if self.size > MAX_LOG_FILE_SIZE {
+            // Nowhere to report a failure to but the terminal, which the log
+            // file is meant to spare
+            let _ = self.reopen();
+        }
+    }
+}
+
+/// Timestamps in local time, which is what someone looking into a failed run
+/// knows its time in
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        write!(
+            w,
+            "{}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f")
+        )
+    }
+}
+
+/// A line for the log file that doesn't come from a log message, in the same
+/// format as those
+fn note_line(level: &str, message: &str) -> String {
+    let timestamp = chrono::Local::now()
+        .format("%Y-%m-%d %H:%M:%S%.3f")
+        .to_string();
+    if JSON_FORMAT.load(Ordering::Relaxed) {
+        let line = serde_json::json!({
+            "timestamp": timestamp,
+            "level": level,
+            "fields": { "message": message },
+            "target": env!("CARGO_CRATE_NAME"),
+        });
+        format!("{line}\n")
+    } else {
+        format!(
+            "{timestamp} {level:>5} {}: {message}\n",
+            env!("CARGO_CRATE_NAME")
+        )
+    }
+}
+
+/// Write to the log file, if there is one
+fn write_to_file(bytes: &[u8]) {
+    let Some(path) = log_file_path() else {
+        return;
+    };
+    let mut open = OPEN_LOG_FILE.lock().unwrap_or_else(|err| err.into_inner());
+    if open.as_ref().map_or(true, |log_file| log_file.path != path) {
+        *open = Some(LogFile::open(&path));
+    }
+    if let Some(log_file) = &mut *open {
+        log_file.write(bytes);
+    }
+}
+
+/// Record an error that ends the program in the log file, where it would
+/// otherwise be missing
+pub fn log_exit_error(message: &str) {
+    write_to_file(note_line("ERROR", message).as_bytes());
+}
+
+/// Where log output goes: stderr, or the log file
+#[derive(Clone, Copy)]
+enum Output {
+    Stderr,
+    File,
+}
+
+/// Writer that masks secrets in each message before passing it on. Messages
+/// come in a single write each.
+struct Redacting(Output);
+
+impl Write for Redacting {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = redact_secrets(&String::from_utf8_lossy(buf));
+        match self.0 {
+            Output::Stderr => io::stderr().write_all(text.as_bytes())?,
+            Output::File => write_to_file(text.as_bytes()),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0 {
+            Output::Stderr => io::stderr().flush(),
+            Output::File => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for Output {
+    type Writer = Redacting;
+
+    fn make_writer(&'a self) -> Redacting {
+        Redacting(*self)
+    }
+}
+
+/// Filter for aochelper's messages up to `level`, and those of the libraries
+/// it uses only at the trace level, and otherwise up to warnings
+fn filter(level: LevelFilter) -> EnvFilter {
+    let libraries = match level {
+        LevelFilter::TRACE => level,
+        _ => level.min(LevelFilter::WARN),
+    };
+    EnvFilter::new(format!("{libraries},{}={level}", env!("CARGO_CRATE_NAME")))
+}
+
+/// Set up logging to stderr, and to the log file once there is one. RUST_LOG
+/// configures both as usual; without it, aochelper's messages up to `level`
+/// are logged, and the log file gets its info messages either way.
+pub fn init(level: LevelFilter, format: LogFormat) {
+    JSON_FORMAT.store(format == LogFormat::Json, Ordering::Relaxed);
+    let from_env = std::env::var_os(EnvFilter::DEFAULT_ENV).is_some();
+    let (stderr_filter, file_filter) = if from_env {
+        (EnvFilter::from_default_env(), EnvFilter::from_default_env())
+    } else {
+        (filter(level), filter(level.max(LevelFilter::INFO)))
+    };
+    let stderr = fmt::layer()
+        .with_writer(Output::Stderr)
+        .with_timer(LocalTime)
+        .with_ansi(color::on_stderr());
+    let file = fmt::layer()
+        .with_writer(Output::File)
+        .with_timer(LocalTime)
+        .with_ansi(false);
+    let layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = match format {
+        LogFormat::Text => vec![
+            stderr.with_filter(stderr_filter).boxed(),
+            file.with_filter(file_filter).boxed(),
+        ],
+        LogFormat::Json => vec![
+            stderr.json().with_filter(stderr_filter).boxed(),
+            file.json().with_filter(file_filter).boxed(),
+        ],
+    };
+    let result = tracing_subscriber::registry().with(layers).try_init();
+    if let Err(err) = result {
+        eprintln!("Failed to set up logging: {err}");
+    }
+}
@@ -0,0 +1,172 @@
+//! Manual pages, generated from the command line definition with a few
+//! sections added by hand: the environment, the files aochelper keeps and
+//! the exit codes
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap_mangen::roff::{bold, roman, Roff};
+use clap_mangen::Man;
+
+use crate::aoc::BASE_URL_ENV_VAR;
+use crate::config::{CONFIG_ENV_VAR, CONFIG_FILE};
+use crate::cookies::SESSION_ENV_VAR;
+use crate::report::{self, say};
+use crate::vault::{ARCHIVE_PASSPHRASE_ENV_VAR, PASSPHRASE_ENV_VAR};
+
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        SESSION_ENV_VAR,
+        "Session key to use instead of the stored one or the browser's.",
+    ),
+    (
+        BASE_URL_ENV_VAR,
+        "Server to use instead of https://adventofcode.com, like --base-url.",
+    ),
+    (
+        CONFIG_ENV_VAR,
+        "Configuration file to use instead of aochelper.toml, like --config.",
+    ),
+    (
+        PASSPHRASE_ENV_VAR,
+        "Passphrase of session keys encrypted with `aochelper session lock`.",
+    ),
+    (
+        ARCHIVE_PASSPHRASE_ENV_VAR,
+        "Passphrase of archives encrypted with `aochelper archive --encrypt`.",
+    ),
+    (
+        "HTTPS_PROXY, HTTP_PROXY, NO_PROXY",
+        "Proxy to go through, unless --proxy or the proxy key is set, and hosts to reach without it.",
+    ),
+    (
+        "RUST_LOG",
+        "How much to log to stderr, e.g. info or debug.",
+    ),
+    ("NO_COLOR", "Don't color the output, like --no-color."),
+    (
+        "XDG_CONFIG_HOME, XDG_DATA_HOME, XDG_CACHE_HOME",
+        "Base directories of the files below, instead of ~/.config, ~/.local/share and ~/.cache.",
+    ),
+];
+
+const FILES: &[(&str, &str)] = &[
+    (
+        CONFIG_FILE,
+        "Configuration of the current directory, unless --config names another file, see aochelper-set(1).",
+    ),
+    (
+        "~/.config/aochelper/secrets.toml",
+        "Session keys, readable only by the user.",
+    ),
+    (
+        "~/.adventofcode.session",
+        "Session key shared with other Advent of Code tools.",
+    ),
+    (
+        "~/.local/share/aochelper/",
+        "History of downloads and answers, cooldowns, the progress of batch downloads and the last check for a new release.",
+    ),
+    ("~/.cache/aochelper/", "Downloaded inputs."),
+];
+
+/// Codes aochelper exits with, in order
+fn exit_codes() -> [(i32, &'static str); 6] {
+    [
+        (1, "Any other error."),
+        (2, "Invalid arguments."),
+        (
+            crate::EXIT_SESSION,
+            "No session key was found, or adventofcode.com rejected it.",
+        ),
+        (crate::EXIT_LOCKED, "The puzzle isn't unlocked yet."),
+        (
+            crate::EXIT_RATE_LIMITED,
+            "Rate-limited: adventofcode.com asked for fewer requests, or the answer cooldown isn't over.",
+        ),
+        (
+            crate::EXIT_NETWORK,
+            "Network error, such as no connection or a timeout.",
+        ),
+    ]
+}
+
+/// Add a section of tagged paragraphs
+fn section<'a>(roff: &mut Roff, title: &str, items: impl IntoIterator<Item = (String, &'a str)>) {
+    roff.control("SH", [title]);
+    for (tag, text) in items {
+        roff.control("TP", []);
+        roff.text([bold(tag)]);
+        roff.text([roman(text)]);
+    }
+}
+
+/// Write the manual page of aochelper itself
+fn render_main(man: &Man, w: &mut dyn Write) -> Result<()> {
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    man.render_options_section(w)?;
+    man.render_subcommands_section(w)?;
+    let mut roff = Roff::new();
+    section(
+        &mut roff,
+        "ENVIRONMENT",
+        ENVIRONMENT
+            .iter()
+            .map(|(name, text)| (name.to_string(), *text)),
+    );
+    section(
+        &mut roff,
+        "FILES",
+        FILES.iter().map(|(path, text)| (path.to_string(), *text)),
+    );
+    section(
+        &mut roff,
+        "EXIT STATUS",
+        exit_codes()
+            .into_iter()
+            .map(|(code, text)| (code.to_string(), text)),
+    );
+    roff.to_writer(w)?;
+    man.render_version_section(w)?;
+    man.render_authors_section(w)?;
+    Ok(())
+}
+
+/// Print the manual page of aochelper, or with `dir`, write it and one page
+/// for each command into that directory
+pub fn man_cmd(command: clap::Command, dir: Option<&Path>) -> Result<()> {
+    let mut command = command.disable_help_subcommand(true);
+    command.build();
+    let Some(dir) = dir else {
+        return render_main(&Man::new(command), &mut std::io::stdout());
+    };
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let mut written = Vec::new();
+    let mut commands: Vec<_> = command.get_subcommands().cloned().collect();
+    while let Some(command) = commands.pop() {
+        if command.is_hide_set() {
+            continue;
+        }
+        commands.extend(command.get_subcommands().cloned());
+        let path = Man::new(command).generate_to(dir)?;
+        written.push(path);
+    }
+    let man = Man::new(command);
+    let path = dir.join(man.get_filename());
+    let mut file =
+        fs::File::create(&path).with_context(|| format!("Failed to write {:?}", path))?;
+    render_main(&man, &mut file)?;
+    written.push(path);
+    written.sort();
+    for path in &written {
+        say!("Wrote {}", path.display());
+    }
+    report::set("pages", written);
+    Ok(())
+}
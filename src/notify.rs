@@ -0,0 +1,222 @@
+//! Desktop notifications, for `wait` and `submit` when nobody is watching the
+//! terminal. They are sent with the tool each platform comes with:
+//! notify-send on Linux, osascript on macOS and PowerShell on Windows.
+//!
+//! Events can also be posted to a webhook, such as a Discord or Slack
+//! channel shared with friends.
+
+use std::fmt;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::{aoc, suggest};
+
+/// Application ID Windows shows toasts from PowerShell under
+#[cfg(windows)]
+const POWERSHELL_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+/// Shows the notification, with its title and text taken from environment
+/// variables so that they need no quoting
+#[cfg(windows)]
+const TOAST_SCRIPT: &str = "\
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null
+$template = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$text = $template.GetElementsByTagName('text')
+$text.Item(0).AppendChild($template.CreateTextNode($env:AOCHELPER_TITLE)) > $null
+$text.Item(1).AppendChild($template.CreateTextNode($env:AOCHELPER_BODY)) > $null
+$toast = [Windows.UI.Notifications.ToastNotification]::new($template)
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier($env:AOCHELPER_APP_ID).Show($toast)";
+
+#[cfg(windows)]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", TOAST_SCRIPT])
+        .env("AOCHELPER_TITLE", title)
+        .env("AOCHELPER_BODY", body)
+        .env("AOCHELPER_APP_ID", POWERSHELL_APP_ID);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn command(title: &str, body: &str) -> Command {
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut command = Command::new("osascript");
+    command.args([
+        "-e",
+        &format!(
+            "display notification {} with title {}",
+            quote(body),
+            quote(title)
+        ),
+    ]);
+    command
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name=aochelper", title, body]);
+    command
+}
+
+fn show(title: &str, body: &str) -> Result<()> {
+    let mut command = command(title, body);
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "{:?} failed: {status}",
+            command.get_program()
+        ));
+    }
+    Ok(())
+}
+
+/// Show a desktop notification if `notifications = true` is set. Failing to
+/// is only worth a warning.
+pub fn send(config: &Config, title: &str, body: &str) {
+    if config.notifications != Some(true) {
+        return;
+    }
+    tracing::debug!("Notifying: {title}: {body}");
+    if let Err(err) = show(title, body) {
+        tracing::warn!("Failed to show a desktop notification: {err:#}");
+    }
+}
+
+/// What can be posted to the webhook
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// `wait` saw the puzzle unlock
+    Unlocked,
+    /// An input was downloaded
+    Downloaded,
+    /// An answer was right
+    Correct,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::Unlocked,
+        WebhookEvent::Downloaded,
+        WebhookEvent::Correct,
+    ];
+
+    fn id(&self) -> &'static str {
+        match self {
+            WebhookEvent::Unlocked => "unlocked",
+            WebhookEvent::Downloaded => "downloaded",
+            WebhookEvent::Correct => "correct",
+        }
+    }
+
+    /// Parse a comma-separated list of events, as given to `set webhook_events`
+    pub fn parse_list(list: &str) -> Result<Vec<WebhookEvent>> {
+        let ids = WebhookEvent::ALL.map(|event| event.id());
+        let mut events = Vec::new();
+        for id in list.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+            let event = WebhookEvent::ALL
+                .into_iter()
+                .find(|event| event.id() == id)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown webhook event '{id}'.{} Events are: {}",
+                        suggest::did_you_mean(id, &ids),
+                        ids.join(", ")
+                    )
+                })?;
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// Check a URL given to `set webhook_url`
+pub fn validate_webhook_url(url: &str) -> Result<()> {
+    let parsed =
+        reqwest::Url::parse(url).with_context(|| format!("'{url}' is not a valid webhook URL"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow::anyhow!(
+            "'{url}' is not a valid webhook URL: it must start with https://"
+        ));
+    }
+    Ok(())
+}
+
+/// What is posted to the webhook. Discord shows `content` and Slack shows
+/// `text`; the other fields are for bots.
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: WebhookEvent,
+    year: u16,
+    day: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part: Option<u8>,
+    content: &'a str,
+    text: &'a str,
+}
+
+fn post(client: &aoc::Client, url: &str, payload: &Payload) -> Result<()> {
+    client
+        .http()?
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(payload)?)
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Post an event to the webhook, if `webhook_url` is set and the event is
+/// one of `webhook_events` (all of them by default). Failing to is only worth
+/// a warning.
+pub fn webhook(
+    client: &aoc::Client,
+    config: &Config,
+    event: WebhookEvent,
+    year: u16,
+    day: u8,
+    part: Option<u8>,
+    message: &str,
+) {
+    let Some(url) = &config.webhook_url else {
+        return;
+    };
+    if !config
+        .webhook_events
+        .as_ref()
+        .map_or(true, |events| events.contains(&event))
+    {
+        return;
+    }
+    let payload = Payload {
+        event,
+        year,
+        day,
+        part,
+        content: message,
+        text: message,
+    };
+    tracing::debug!("Posting {event} to the webhook: {message}");
+    if let Err(err) = post(client, url, &payload) {
+        tracing::warn!("Failed to post to the webhook: {err:#}");
+    }
+}
@@ -0,0 +1,126 @@
+//! Keep files holding the session key private to the current user: mode 0600
+//! on Unix, and a DACL granting access to the owner only on Windows.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Write `contents` to `path`, readable and writable by the current user only
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {path:?}"))?;
+    // the mode only applies to new files
+    restrict(path)?;
+    std::io::Write::write_all(&mut file, contents)
+        .with_context(|| format!("Failed to write {path:?}"))
+}
+
+/// Make an existing file readable and writable by the current user only
+#[cfg(unix)]
+pub fn restrict(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict permissions of {path:?}"))
+}
+
+/// Make an existing file accessible by its owner only, by replacing its DACL
+/// with one that doesn't inherit from the parent directory
+#[cfg(windows)]
+pub fn restrict(path: &Path) -> Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SetNamedSecurityInfoW,
+        SDDL_REVISION_1, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        GetSecurityDescriptorDacl, ACL, DACL_SECURITY_INFORMATION,
+        PROTECTED_DACL_SECURITY_INFORMATION,
+    };
+
+    fn wide(s: &std::ffi::OsStr) -> Vec<u16> {
+        s.encode_wide().chain(Some(0)).collect()
+    }
+
+    // full access for the owner and SYSTEM, nothing inherited
+    let sddl = wide("D:P(A;;FA;;;OW)(A;;FA;;;SY)".as_ref());
+    let path_w = wide(path.as_os_str());
+    // SAFETY: sddl and path_w are null-terminated wide strings that outlive
+    // the calls. The descriptor is allocated by the conversion, which has
+    // succeeded when it is used; dacl points into it and is only used before
+    // the descriptor is freed with LocalFree, exactly once, on every path
+    // after the conversion.
+    unsafe {
+        let mut descriptor = ptr::null_mut();
+        if ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            ptr::null_mut(),
+        ) == 0
+        {
+            return Err(std::io::Error::last_os_error())
+                .context("Failed to build security descriptor");
+        }
+        let mut present = 0;
+        let mut defaulted = 0;
+        let mut dacl: *mut ACL = ptr::null_mut();
+        let result = if GetSecurityDescriptorDacl(
+            descriptor,
+            &mut present,
+            &mut dacl,
+            &mut defaulted,
+        ) == 0
+        {
+            Err(std::io::Error::last_os_error())
+        } else {
+            match SetNamedSecurityInfoW(
+                path_w.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                dacl,
+                ptr::null(),
+            ) {
+                ERROR_SUCCESS => Ok(()),
+                err => Err(std::io::Error::from_raw_os_error(err as i32)),
+            }
+        };
+        LocalFree(descriptor as _);
+        result.with_context(|| format!("Failed to restrict permissions of {path:?}"))
+    }
+}
+
+/// Print a warning if other users can read `path`. Only checked on Unix, where
+/// a file left with the default umask is typically world readable.
+pub fn warn_if_exposed(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            let mode = metadata.permissions().mode();
+            if mode & 0o077 != 0 {
+                eprintln!(
+                    "{} {path:?} contains your session key but can be read by other users \
+                     (mode {:o}). Run `chmod 600 {}` to fix this.",
+                    crate::color::err("WARNING:", crate::color::WARNING),
+                    mode & 0o777,
+                    path.display()
+                );
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
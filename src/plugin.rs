@@ -0,0 +1,73 @@
+//! External subcommands: like cargo and git, `aochelper <name>` runs an
+//! `aochelper-<name>` executable found on PATH when there is no such built-in
+//! command, so that others can extend aochelper without forking it.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+pub const PREFIX: &str = "aochelper-";
+
+/// Error returned when a plugin exits with a failure, so that aochelper
+/// exits with the same code
+#[derive(Debug)]
+pub struct PluginFailed {
+    pub name: String,
+    pub code: Option<i32>,
+}
+
+impl fmt::Display for PluginFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{PREFIX}{} exited with code {code}", self.name),
+            None => write!(f, "{PREFIX}{} was terminated by a signal", self.name),
+        }
+    }
+}
+
+impl std::error::Error for PluginFailed {}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Path of the `aochelper-<name>` executable on PATH, if there is one
+pub fn find(name: &OsStr) -> Option<PathBuf> {
+    let mut file_name = OsString::from(PREFIX);
+    file_name.push(name);
+    file_name.push(env::consts::EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
+/// Run the plugin at `path` with `args`, adding `vars` to its environment,
+/// and wait for it to finish
+pub fn run(path: &Path, name: &OsStr, args: &[OsString], vars: &[(&str, OsString)]) -> Result<()> {
+    tracing::debug!("Running {}", path.display());
+    let status = Command::new(path)
+        .args(args)
+        .envs(vars.iter().map(|(key, value)| (key, value)))
+        .status()
+        .with_context(|| format!("Failed to run {}", path.display()))?;
+    if !status.success() {
+        return Err(PluginFailed {
+            name: name.to_string_lossy().into_owned(),
+            code: status.code(),
+        }
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Progress bar for commands that make many requests. It is drawn on stderr
+//! when that is a terminal, except in JSON mode.
+
+use std::fmt;
+use std::sync::Mutex;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::report;
+
+/// The progress bar being shown, if any
+static BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Show a progress bar for `len` items, until [`finish`]
+pub fn start(len: usize) -> ProgressBar {
+    let bar = if report::json() {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(len as u64)
+    };
+    let style = ProgressStyle::with_template("{bar:30} {pos}/{len} {msg} (ETA {eta})")
+        .unwrap_or_else(|_| ProgressStyle::default_bar());
+    bar.set_style(style);
+    *BAR.lock().unwrap_or_else(|err| err.into_inner()) = Some(bar.clone());
+    bar
+}
+
+/// Remove the progress bar
+pub fn finish() {
+    if let Some(bar) = BAR.lock().unwrap_or_else(|err| err.into_inner()).take() {
+        bar.finish_and_clear();
+    }
+}
+
+/// Print a line on stderr, above the progress bar if one is shown
+pub fn note(line: fmt::Arguments) {
+    match &*BAR.lock().unwrap_or_else(|err| err.into_inner()) {
+        Some(bar) if !bar.is_hidden() => bar.println(line.to_string()),
+        _ => eprintln!("{line}"),
+    }
+}
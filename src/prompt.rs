@@ -0,0 +1,66 @@
+//! Questions on the terminal. With --yes, none are asked, for scripts that
+//! can't answer them.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+use crate::report::{self, say};
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Never ask anything, for --yes
+pub fn set_non_interactive(non_interactive: bool) {
+    NON_INTERACTIVE.store(non_interactive, Ordering::Relaxed);
+}
+
+/// Whether questions may be asked at all
+pub fn allowed() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// Whether to ask for something that is missing rather than fail: only when
+/// someone is there to answer, and the questions won't end up in the output
+pub fn interactive() -> bool {
+    let output = if report::json() {
+        io::stderr().is_terminal()
+    } else {
+        io::stdout().is_terminal()
+    };
+    allowed() && io::stdin().is_terminal() && output
+}
+
+/// Ask a question on the terminal and return the trimmed answer
+pub fn ask(question: &str) -> Result<String> {
+    if !allowed() {
+        return Err(anyhow::anyhow!(
+            "Can't ask \"{question}\" with --yes. Give the answer on the command line or in the configuration instead."
+        ));
+    }
+    // With --json, stdout is kept for the result
+    if report::json() {
+        eprint!("{question} ");
+    } else {
+        print!("{question} ");
+        io::stdout().flush()?;
+    }
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(anyhow::anyhow!("No answer given, aborting."));
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Ask a yes/no question, with `default` chosen by an empty answer
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match ask(&format!("{question} {hint}"))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => say!("Please answer y or n."),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Puzzle sites aochelper can talk to. Each provider knows how to build the
+//! URLs of its puzzles and which cookie holds its session key; caching,
+//! configuration and output work the same for all of them.
+
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+
+use crate::aoc::NotUnlocked;
+use crate::suggest;
+
+/// A site with advent-style puzzles
+pub trait Provider: Sync {
+    /// Name to select the provider with, in --provider or aochelper.toml
+    fn name(&self) -> &'static str;
+
+    /// URL of the site, unless overridden with --base-url
+    fn default_url(&self) -> &'static str;
+
+    /// Host under which browsers store the session cookie
+    fn cookie_host(&self) -> &'static str;
+
+    /// Name of the cookie that holds the session key
+    fn cookie_name(&self) -> &'static str;
+
+    /// Check that a puzzle exists, before asking the site for it
+    fn check_puzzle(&self, year: u16, day: u8) -> Result<()>;
+
+    /// Latest year with puzzles out
+    fn max_year(&self) -> u16;
+
+    /// Paths of the puzzle's input, description and answer form
+    fn input_path(&self, year: u16, day: u8) -> String;
+    fn page_path(&self, year: u16, day: u8) -> String;
+    fn answer_path(&self, year: u16, day: u8) -> String;
+
+    /// Paths of the leaderboard and statistics of a year
+    fn leaderboard_path(&self, year: u16) -> String;
+    fn stats_path(&self, year: u16) -> String;
+
+    /// When a puzzle becomes available
+    fn unlock_time(&self, year: u16, day: u8) -> Option<DateTime<Utc>>;
+
+    /// Year and day of the puzzle that unlocked today, while the event runs
+    fn current_puzzle(&self) -> Option<(u16, u8)>;
+}
+
+pub struct AdventOfCode;
+
+impl Provider for AdventOfCode {
+    fn name(&self) -> &'static str {
+        "aoc"
+    }
+
+    fn default_url(&self) -> &'static str {
+        "https://adventofcode.com"
+    }
+
+    fn cookie_host(&self) -> &'static str {
+        ".adventofcode.com"
+    }
+
+    fn cookie_name(&self) -> &'static str {
+        "session"
+    }
+
+    fn check_puzzle(&self, year: u16, day: u8) -> Result<()> {
+        let max_year = self.max_year();
+        if year == max_year + 1 {
+            Err(NotUnlocked(format!(
+                "Advent of Code {year} hasn't started yet. It starts on December 1."
+            ))
+            .into())
+        } else if !(2015..=max_year).contains(&year) {
+            Err(anyhow::anyhow!("Invalid year: {year}"))
+        } else if !(1..=25).contains(&day) {
+            Err(anyhow::anyhow!("Invalid day: {day}"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The current year from December 1, EST, and the year before until then
+    fn max_year(&self) -> u16 {
+        let now = (Utc::now() - chrono::Duration::hours(5)).date_naive();
+        let year = if now.month() == 12 {
+            now.year()
+        } else {
+            now.year() - 1
+        };
+        u16::try_from(year).unwrap_or(u16::MAX)
+    }
+
+    fn input_path(&self, year: u16, day: u8) -> String {
+        format!("/{year}/day/{day}/input")
+    }
+
+    fn page_path(&self, year: u16, day: u8) -> String {
+        format!("/{year}/day/{day}")
+    }
+
+    fn answer_path(&self, year: u16, day: u8) -> String {
+        format!("/{year}/day/{day}/answer")
+    }
+
+    fn leaderboard_path(&self, year: u16) -> String {
+        format!("/{year}/leaderboard")
+    }
+
+    fn stats_path(&self, year: u16) -> String {
+        format!("/{year}/stats")
+    }
+
+    /// At midnight EST (05:00 UTC) on the puzzle's day in December
+    fn unlock_time(&self, year: u16, day: u8) -> Option<DateTime<Utc>> {
+        let unlock = NaiveDate::from_ymd_opt(year.into(), 12, day.into())?.and_hms_opt(5, 0, 0)?;
+        Some(Utc.from_utc_datetime(&unlock))
+    }
+
+    /// Today's date in EST, from December 1 to 25
+    fn current_puzzle(&self) -> Option<(u16, u8)> {
+        let today = (Utc::now() - chrono::Duration::hours(5)).date_naive();
+        let year = u16::try_from(today.year()).ok()?;
+        let day = u8::try_from(today.day()).ok()?;
+        (today.month() == 12 && (1..=25).contains(&day)).then_some((year, day))
+    }
+}
+
+/// All known providers
+pub const PROVIDERS: &[&dyn Provider] = &[&AdventOfCode];
+
+/// Provider given with --provider, which takes precedence over aochelper.toml
+static OVERRIDE: Mutex<Option<&'static dyn Provider>> = Mutex::new(None);
+/// Provider from aochelper.toml
+static CONFIGURED: Mutex<Option<&'static dyn Provider>> = Mutex::new(None);
+
+pub fn find(name: &str) -> Result<&'static dyn Provider> {
+    PROVIDERS
+        .iter()
+        .find(|provider| provider.name() == name)
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<_> = PROVIDERS.iter().map(|provider| provider.name()).collect();
+            anyhow::anyhow!(
+                "Unknown provider '{name}'.{} Known providers are: {}",
+                suggest::did_you_mean(name, &names),
+                names.join(", ")
+            )
+        })
+}
+
+pub fn set_override(name: Option<&str>) -> Result<()> {
+    let provider = name.map(find).transpose()?;
+    *OVERRIDE.lock().unwrap_or_else(|err| err.into_inner()) = provider;
+    Ok(())
+}
+
+pub fn set_configured(name: Option<&str>) -> Result<()> {
+    let provider = name.map(find).transpose()?;
+    *CONFIGURED.lock().unwrap_or_else(|err| err.into_inner()) = provider;
+    Ok(())
+}
+
+/// The selected provider, Advent of Code by default
+pub fn active() -> &'static dyn Provider {
+    let selected = *OVERRIDE.lock().unwrap_or_else(|err| err.into_inner());
+    selected
+        .or_else(|| *CONFIGURED.lock().unwrap_or_else(|err| err.into_inner()))
+        .unwrap_or(&AdventOfCode)
+}
@@ -0,0 +1,510 @@
+//! Conversion of puzzle descriptions from HTML to Markdown or plain text, and
+//! the examples in them
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::aoc;
+
+pub const EXAMPLE_ANSWERS_FILE: &str = "examples.toml";
+
+/// Format in which puzzle descriptions are saved
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Md,
+    Txt,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Md => "md",
+            Format::Txt => "txt",
+        }
+    }
+
+    /// Convert the `<article>` elements of a puzzle page to this format
+    pub fn convert(self, html: &str) -> String {
+        match self {
+            Format::Html => html.to_string(),
+            Format::Md => Converter::new(true).run(html),
+            Format::Txt => Converter::new(false).run(html),
+        }
+    }
+}
+
+/// Replace the HTML entities that show up on Advent of Code pages
+pub fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Walks the tags of a puzzle description and writes out its text. Only the
+/// handful of elements Advent of Code uses are handled: headings, paragraphs,
+/// emphasis, inline code, code blocks, lists and links. Any other tag is
+/// dropped and its text kept.
+struct Converter {
+    markdown: bool,
+    out: String,
+    /// Inside `<pre>`, where the text is kept as it is
+    pre: bool,
+    /// Text of the `<code>` element being read, if any, and whether it is
+    /// emphasized as a whole
+    code: Option<(String, bool)>,
+    /// Depth of nested lists
+    lists: usize,
+    /// Targets of the links being read
+    links: Vec<String>,
+}
+
+impl Converter {
+    fn new(markdown: bool) -> Self {
+        Converter {
+            markdown,
+            out: String::new(),
+            pre: false,
+            code: None,
+            lists: 0,
+            links: Vec::new(),
+        }
+    }
+
+    fn run(mut self, html: &str) -> String {
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            self.text(&rest[..start]);
+            let Some(end) = rest[start..].find('>') else {
+                rest = &rest[start..];
+                break;
+            };
+            self.tag(&rest[start + 1..start + end]);
+            rest = &rest[start + end + 1..];
+        }
+        self.text(rest);
+        self.out.trim_end().to_string() + "\n"
+    }
+
+    fn push(&mut self, s: &str) {
+        match &mut self.code {
+            Some((code, _)) => code.push_str(s),
+            None => self.out.push_str(s),
+        }
+    }
+
+    /// End the current line, with `blank` lines after it, unless that was
+    /// already done
+    fn newline(&mut self, blank: usize) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if self.out.is_empty() {
+            return;
+        }
+        let ends = self.out.len() - self.out.trim_end_matches('\n').len();
+        for _ in ends..=blank {
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = unescape(text);
+        if self.pre {
+            self.push(&text);
+            return;
+        }
+        let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let line_start = self.code.is_none() && (self.out.is_empty() || self.out.ends_with('\n'));
+        if text.starts_with(char::is_whitespace) && !line_start && !collapsed.is_empty() {
+            collapsed.insert(0, ' ');
+        }
+        if text.ends_with(char::is_whitespace) && !(line_start && collapsed.is_empty()) {
+            collapsed.push(' ');
+        }
+        self.push(&collapsed);
+    }
+
+    fn tag(&mut self, tag: &str) {
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let md = self.markdown;
+        match (name.as_str(), closing) {
+            ("h2", false) => {
+                self.newline(1);
+                if md {
+                    self.out.push_str("## ");
+                }
+            }
+            ("h2" | "p", true) | ("p", false) => self.newline(1),
+            ("pre", false) => {
+                self.newline(1);
+                self.pre = true;
+                if md {
+                    self.out.push_str("```\n");
+                }
+            }
+            ("pre", true) => {
+                self.pre = false;
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                if md {
+                    self.out.push_str("```\n");
+                }
+                self.newline(1);
+            }
+            ("code", false) if !self.pre => self.code = Some((String::new(), false)),
+            ("code", true) if !self.pre => {
+                if let Some((code, emphasized)) = self.code.take() {
+                    match (md, emphasized) {
+                        (true, true) => self.out.push_str(&format!("*`{code}`*")),
+                        (true, false) => self.out.push_str(&format!("`{code}`")),
+                        (false, _) => self.out.push_str(&code),
+                    }
+                }
+            }
+            ("em", _) if self.pre || !md => (),
+            ("em", false) => match &mut self.code {
+                // Markdown has no emphasis inside code spans, so emphasize the
+                // whole span instead
+                Some((code, emphasized)) if code.is_empty() => *emphasized = true,
+                Some(_) => (),
+                None => self.out.push('*'),
+            },
+            ("em", true) if self.code.is_some() => (),
+            ("em", true) => self.out.push('*'),
+            ("ul" | "ol", false) => {
+                self.newline(if self.lists == 0 { 1 } else { 0 });
+                self.lists += 1;
+            }
+            ("ul" | "ol", true) => {
+                self.lists = self.lists.saturating_sub(1);
+                self.newline(if self.lists == 0 { 1 } else { 0 });
+            }
+            ("li", false) => {
+                self.newline(0);
+                let indent = "  ".repeat(self.lists.saturating_sub(1));
+                self.out.push_str(&format!("{indent}- "));
+            }
+            ("a", false) => {
+                let href = attribute(tag, "href").unwrap_or_default();
+                let href = if href.starts_with('/') {
+                    format!("{}{href}", aoc::base_url())
+                } else {
+                    href
+                };
+                self.links.push(href);
+                if md {
+                    self.push("[");
+                }
+            }
+            ("a", true) => {
+                let href = self.links.pop().unwrap_or_default();
+                if md {
+                    self.push(&format!("]({href})"));
+                }
+            }
+            ("br", _) => self.newline(0),
+            _ => (),
+        }
+    }
+}
+
+/// Value of an attribute of a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let (_, value) = tag.split_once(&format!("{name}=\""))?;
+    let (value, _) = value.split_once('"')?;
+    Some(unescape(value))
+}
+
+/// The example inputs of a puzzle: the first code block after each "For
+/// example" in the description
+pub fn examples(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut examples: Vec<String> = Vec::new();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find("for example") {
+        let after = from + found;
+        let Some(start) = lower[after..].find("<pre><code>") else {
+            break;
+        };
+        let start = after + start + "<pre><code>".len();
+        let Some(len) = lower[start..].find("</code></pre>") else {
+            break;
+        };
+        let example = unescape(&strip_tags(&html[start..start + len]));
+        if !examples.contains(&example) {
+            examples.push(example);
+        }
+        from = start + len;
+    }
+    examples
+}
+
+/// Title of a puzzle, from the `--- Day 5: Supply Stacks ---` heading of its
+/// description, whether in HTML, Markdown or plain text
+pub fn title(description: &str) -> Option<String> {
+    let start = description.find("--- Day ")?;
+    let heading = &description[start + "--- Day ".len()..];
+    let heading = &heading[..heading.find(" ---")?];
+    let (_, title) = heading.split_once(": ")?;
+    Some(unescape(&strip_tags(title)).trim().to_string()).filter(|title| !title.is_empty())
+}
+
+/// Text of a piece of HTML, with the tags left out
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => (),
+        }
+    }
+    text
+}
+
+/// The expected answer to the example of each part in a puzzle description:
+/// the last emphasized code in the part's `<article>`
+pub fn example_answers(html: &str) -> Vec<Option<String>> {
+    html.split("</article>")
+        .filter(|part| part.contains("<article"))
+        .map(|part| {
+            let last = |open: &str, close: &str| {
+                let start = part.rfind(open)? + open.len();
+                let len = part[start..].find(close)?;
+                Some((start, &part[start..start + len]))
+            };
+            let answer = match (
+                last("<code><em>", "</em></code>"),
+                last("<em><code>", "</code></em>"),
+            ) {
+                (Some(a), Some(b)) => Some(a.max(b).1),
+                (a, b) => a.or(b).map(|(_, answer)| answer),
+            }?;
+            Some(unescape(&strip_tags(answer)).trim().to_string())
+        })
+        .collect()
+}
+
+/// Expected answers to the examples, kept in examples.toml next to them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExampleAnswers(BTreeMap<String, BTreeMap<String, PartAnswers>>);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PartAnswers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part2: Option<String>,
+}
+
+impl ExampleAnswers {
+    pub fn load(path: &Path) -> Result<ExampleAnswers> {
+        if !path.exists() {
+            return Ok(ExampleAnswers::default());
+        }
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Drop the answers of a puzzle. Returns whether there were any.
+    pub fn remove(&mut self, year: u16, day: u8) -> bool {
+        let Some(days) = self.0.get_mut(&year.to_string()) else {
+            return false;
+        };
+        let removed = days.remove(&format!("{day:02}")).is_some();
+        if days.is_empty() {
+            self.0.remove(&year.to_string());
+        }
+        removed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Keep only the answers of puzzles of `year`
+    pub fn retain_year(&mut self, year: u16) {
+        self.0
+            .retain(|answers_year, _| *answers_year == year.to_string());
+    }
+
+    /// Add the answers of puzzles that have none here, or with `overwrite`,
+    /// replace them. Returns how many puzzles' answers were added.
+    pub fn merge(&mut self, other: ExampleAnswers, overwrite: bool) -> usize {
+        let mut added = 0;
+        for (year, days) in other.0 {
+            let entry = self.0.entry(year).or_default();
+            for (day, answers) in days {
+                if overwrite || !entry.contains_key(&day) {
+                    entry.insert(day, answers);
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
+
+    /// Set the answers found for a puzzle, keeping ones that weren't found
+    pub fn set(&mut self, year: u16, day: u8, answers: &[Option<String>]) {
+        let entry = self
+            .0
+            .entry(year.to_string())
+            .or_default()
+            .entry(format!("{day:02}"))
+            .or_default();
+        for (part, answer) in answers.iter().enumerate() {
+            let field = match part {
+                0 => &mut entry.part1,
+                1 => &mut entry.part2,
+                _ => break,
+            };
+            if answer.is_some() {
+                field.clone_from(answer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<article class="day-desc"><h2>--- Day 1: Calorie Counting ---</h2>
+<p>The Elves take turns writing down the number of <em>Calories</em> in each item.</p>
+<p>For example, suppose the Elves finish writing their items' Calories and end up with the following list:</p>
+<pre><code>1000
+2000
+
+4000
+</code></pre>
+<p>This list represents the Calories of the food carried by the Elves:</p>
+<ul>
+<li>The first Elf is carrying food with <code>1000</code> and <code>2000</code> Calories.</li>
+<li>The second Elf is carrying <a href="https://example.com/food">one food item</a>.</li>
+</ul>
+<p>In the example above, this is <em><code>24000</code></em> Calories.</p>
+</article>
+<p>Your puzzle answer was <code>69528</code>.</p>
+<article class="day-desc"><h2 id="part2">--- Part Two ---</h2>
+<p>For example, the top three Elves are carrying <code>24000</code>, <code>11000</code> and <code>10000</code> Calories, a sum of <code><em>45000</em></code> Calories &amp; more.</p>
+</article>"#;
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            Format::Md.convert(PAGE),
+            "## --- Day 1: Calorie Counting ---\n\
+             \n\
+             The Elves take turns writing down the number of *Calories* in each item.\n\
+             \n\
+             For example, suppose the Elves finish writing their items' Calories and end up \
+             with the following list:\n\
+             \n\
+             ```\n\
+             1000\n\
+             2000\n\
+             \n\
+             4000\n\
+             ```\n\
+             \n\
+             This list represents the Calories of the food carried by the Elves:\n\
+             \n\
+             - The first Elf is carrying food with `1000` and `2000` Calories.\n\
+             - The second Elf is carrying [one food item](https://example.com/food).\n\
+             \n\
+             In the example above, this is *`24000`* Calories.\n\
+             \n\
+             Your puzzle answer was `69528`.\n\
+             \n\
+             ## --- Part Two ---\n\
+             \n\
+             For example, the top three Elves are carrying `24000`, `11000` and `10000` \
+             Calories, a sum of *`45000`* Calories & more.\n"
+        );
+    }
+
+    #[test]
+    fn plain_text() {
+        let text = Format::Txt.convert(PAGE);
+        assert!(text.starts_with(
+            "--- Day 1: Calorie Counting ---\n\
+             \n\
+             The Elves take turns writing down the number of Calories in each item.\n"
+        ));
+        assert!(text.contains("\n1000\n2000\n\n4000\n\n"));
+        assert!(text.contains("\n- The second Elf is carrying one food item.\n"));
+        assert!(!text.contains(['*', '`', '[']));
+    }
+
+    #[test]
+    fn html_is_kept() {
+        assert_eq!(Format::Html.convert(PAGE), PAGE);
+    }
+
+    #[test]
+    fn nested_lists() {
+        let html = "<ul><li>one<ul><li>two</li></ul></li><li>three</li></ul>";
+        assert_eq!(Format::Md.convert(html), "- one\n  - two\n- three\n");
+    }
+
+    #[test]
+    fn examples_after_for_example() {
+        assert_eq!(examples(PAGE), ["1000\n2000\n\n4000\n"]);
+    }
+
+    #[test]
+    fn examples_are_unescaped_and_deduplicated() {
+        let html = "<p>For example:</p><pre><code>a &lt; <em>b</em></code></pre>\
+                    <p>For example, again:</p><pre><code>a &lt; b</code></pre>\
+                    <pre><code>not an example</code></pre>";
+        assert_eq!(examples(html), ["a < b"]);
+    }
+
+    #[test]
+    fn no_examples() {
+        assert!(examples("<p>Nothing to see.</p><pre><code>1</code></pre>").is_empty());
+    }
+
+    #[test]
+    fn example_answer_of_each_part() {
+        assert_eq!(
+            example_answers(PAGE),
+            [Some("24000".to_string()), Some("45000".to_string())]
+        );
+    }
+
+    #[test]
+    fn part_without_example_answer() {
+        let html = "<article><p>No answer.</p></article>\
+                    <article><p>It is <code><em>7</em></code>.</p></article>";
+        assert_eq!(example_answers(html), [None, Some("7".to_string())]);
+    }
+
+    #[test]
+    fn title_of_description() {
+        assert_eq!(title(PAGE).as_deref(), Some("Calorie Counting"));
+        assert_eq!(title("no heading"), None);
+    }
+}
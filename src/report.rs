@@ -0,0 +1,77 @@
+//! Output of commands: text for people, or with `--json`, a single JSON object
+//! on stdout for editors and scripts. In JSON mode, the text goes to stderr
+//! instead, and commands add what they did to the object with [`set`].
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::logging;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Fields of the JSON result
+static FIELDS: Mutex<Vec<(String, Value)>> = Mutex::new(Vec::new());
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Print a line of text for people, see [`say`]
+pub fn say_line(line: fmt::Arguments) {
+    if json() {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
+}
+
+/// Like `println!`, except that the line goes to stderr with `--json`, to keep
+/// stdout for the JSON result
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::report::say_line(format_args!($($arg)*))
+    };
+}
+pub(crate) use say;
+
+/// Add a field to the JSON result, replacing one of the same name
+pub fn set(key: &str, value: impl Serialize) {
+    let value = match serde_json::to_value(value) {
+        Ok(value) => value,
+        Err(err) => {
+            tracing::warn!("Failed to add {key} to the JSON output: {err}");
+            return;
+        }
+    };
+    let mut fields = FIELDS.lock().unwrap_or_else(|err| err.into_inner());
+    match fields.iter_mut().find(|(name, _)| name == key) {
+        Some((_, old)) => *old = value,
+        None => fields.push((key.to_string(), value)),
+    }
+}
+
+/// With `--json`, print the result of `command`: whether it succeeded, the
+/// error if it didn't, and the fields it set
+pub fn finish(command: &str, result: &anyhow::Result<()>) {
+    if !json() {
+        return;
+    }
+    let mut object = Map::new();
+    object.insert("command".to_string(), command.into());
+    object.insert("ok".to_string(), result.is_ok().into());
+    if let Err(err) = result {
+        let error = logging::redact_secrets(&format!("{err:#}"));
+        object.insert("error".to_string(), error.into());
+    }
+    let fields = std::mem::take(&mut *FIELDS.lock().unwrap_or_else(|err| err.into_inner()));
+    object.extend(fields);
+    println!("{}", Value::Object(object));
+}
@@ -0,0 +1,433 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::aoc;
+use crate::config::{config_file, read_config, CachedKey, Config, KeyStore, StoredKey};
+use crate::cookies::CookieSource;
+use crate::report::say;
+use crate::{color, cookies, logging, permissions, prompt, report, setup, vault};
+
+/// A session key and a description of where it was found
+pub struct Session {
+    pub key: String,
+    pub source: String,
+    /// When the session was started, if known
+    pub created: Option<DateTime<Utc>>,
+    pub origin: Origin,
+}
+
+/// How a session key was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// Given on the command line or in $AOC_SESSION, or stored by the user
+    Stored,
+    /// Read from the named browser's cookies just now
+    Browser(String),
+    /// Read from a browser's cookies earlier, and cached in the secrets file
+    Cached,
+}
+
+/// Advent of Code sessions last about a month; warn when they get this old
+const SESSION_WARN_DAYS: i64 = 25;
+
+impl Session {
+    pub fn age_days(&self) -> Option<i64> {
+        self.created
+            .map(|created| (Utc::now() - created).num_days())
+    }
+
+    /// Print a warning if the session is about to expire
+    pub fn warn_if_old(&self) {
+        if let Some(days) = self.age_days().filter(|&days| days >= SESSION_WARN_DAYS) {
+            eprintln!(
+                "{} the session key from {} is {days} days old and may expire soon. \
+                 Log into {} again and update it.",
+                color::err("Warning:", color::WARNING),
+                self.source,
+                aoc::site()
+            );
+        }
+    }
+}
+
+/// File other Advent of Code tools such as aoc-cli and cargo-aoc read the
+/// session key from, relative to the home directory
+const SESSION_FILE: &str = ".adventofcode.session";
+
+/// Read the session key from ~/.adventofcode.session, if it exists. The
+/// session is taken to be as old as the file.
+fn read_session_file() -> Result<Option<Session>> {
+    let home = match env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }) {
+        Some(home) => home,
+        None => return Ok(None),
+    };
+    let session_path = Path::new(&home).join(SESSION_FILE);
+    if !session_path.exists() {
+        return Ok(None);
+    }
+    permissions::warn_if_exposed(&session_path);
+    let contents = fs::read_to_string(&session_path)
+        .with_context(|| format!("Failed to read {:?}", &session_path))?;
+    let key = contents.trim().trim_start_matches("session=");
+    if key.is_empty() {
+        tracing::debug!("Ignoring empty {session_path:?}");
+        return Ok(None);
+    }
+    tracing::debug!("Found session key in {session_path:?}");
+    let created = fs::metadata(&session_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Utc>::from);
+    Ok(Some(Session {
+        key: key.to_string(),
+        source: session_path.display().to_string(),
+        created,
+        origin: Origin::Stored,
+    }))
+}
+
+/// Find the session key to use: the one given on the command line or in
+/// $AOC_SESSION, the one stored for the selected account in aochelper.toml or
+/// the secrets file, the one in ~/.adventofcode.session if no account is
+/// selected, or finally the one stored by the selected browser.
+#[tracing::instrument(name = "session", skip_all, fields(account = config.active_account()))]
+pub fn resolve_session_key(
+    client: &aoc::Client,
+    session_key: &Option<String>,
+    config: &Config,
+    selection: cookies::BrowserSelection,
+) -> Result<Session> {
+    let session = match find_session(session_key, config, selection) {
+        Err(err) if err.is::<cookies::NoSessionKey>() && prompt::interactive() => Session {
+            key: setup::ask_missing_session_key(client, config.active_account())?,
+            source: "the terminal".to_string(),
+            created: None,
+            origin: Origin::Stored,
+        },
+        result => result?,
+    };
+    logging::register_secret(&session.key);
+    Ok(session)
+}
+
+/// Find the session key like [`resolve_session_key`], but without asking for
+/// one if none is found
+pub fn stored_session_key(
+    config: &Config,
+    selection: cookies::BrowserSelection,
+) -> Result<Session> {
+    let session = find_session(&None, config, selection)?;
+    logging::register_secret(&session.key);
+    Ok(session)
+}
+
+fn find_session(
+    session_key: &Option<String>,
+    config: &Config,
+    selection: cookies::BrowserSelection,
+) -> Result<Session> {
+    if let Some(key) = session_key {
+        return Ok(Session {
+            key: key.clone(),
+            source: "command line".to_string(),
+            created: None,
+            origin: Origin::Stored,
+        });
+    }
+    if let Ok((cookie, source)) = cookies::EnvSource.read_cookie() {
+        tracing::debug!("Found session key in {source}");
+        return Ok(Session {
+            key: cookie.value,
+            source,
+            created: None,
+            origin: Origin::Stored,
+        });
+    }
+    match config.session_key() {
+        Some(StoredKey {
+            key,
+            created,
+            store,
+        }) => {
+            tracing::debug!("Found session key in {store}");
+            Ok(Session {
+                key: vault::reveal(key)?,
+                source: match config.active_account() {
+                    Some(account) => format!("account '{account}' in {store}"),
+                    None => store.to_string(),
+                },
+                created,
+                origin: Origin::Stored,
+            })
+        }
+        None => {
+            // ~/.adventofcode.session can't tell accounts apart
+            let session_file = match config.active_account() {
+                Some(_) => None,
+                None => read_session_file()?,
+            };
+            if let Some(session) = session_file {
+                return Ok(session);
+            }
+            let keyring = cookies::keyring::KeyringSource {
+                account: config.active_account().unwrap_or("default"),
+            };
+            match keyring.read_cookie() {
+                Ok((cookie, source)) => {
+                    tracing::debug!("Found session key in {source}");
+                    return Ok(Session {
+                        key: cookie.value,
+                        source,
+                        created: None,
+                        origin: Origin::Stored,
+                    });
+                }
+                Err(err) => tracing::debug!("{err:#}"),
+            }
+            if let Some(cached) = config
+                .cached_key()
+                .filter(|cached| !selection.disabled && cached.matches(&selection))
+            {
+                tracing::debug!("Using session key cached from {}", cached.browser_name);
+                return Ok(Session {
+                    key: vault::reveal(&cached.session_key)?,
+                    source: format!(
+                        "{} cookies, cached in {}",
+                        cached.browser_name,
+                        KeyStore::User
+                    ),
+                    created: cached.created,
+                    origin: Origin::Cached,
+                });
+            }
+            tracing::debug!("No session key found in config, {SESSION_FILE} or the keyring, attempting to read from browser cookie store");
+            read_browser_session(selection)
+        }
+    }
+}
+
+fn read_browser_session(selection: cookies::BrowserSelection) -> Result<Session> {
+    let (cookie, browser) = cookies::find_session_key(selection)?;
+    logging::register_secret(&cookie.value);
+    Ok(Session {
+        key: cookie.value,
+        source: format!("{browser} cookies"),
+        created: cookie.created,
+        origin: Origin::Browser(browser),
+    })
+}
+
+/// Cache a session key that was just read from a browser and worked, so the
+/// browser's cookies don't have to be read next time.
+pub fn cache_session(
+    session: &Session,
+    account: Option<&str>,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let Origin::Browser(browser_name) = &session.origin else {
+        return Ok(());
+    };
+    let mut config = read_config(config_file())?;
+    config.select_account(account, true)?;
+    config.set_cached_key(Some(CachedKey {
+        session_key: session.key.clone(),
+        created: session.created,
+        browser_name: browser_name.clone(),
+        browser: selection.browser.map(str::to_string),
+        profile: selection.profile.map(str::to_string),
+        container: selection.container.map(str::to_string),
+    }));
+    config.write_store(KeyStore::User)?;
+    tracing::debug!("Cached session key in {}", KeyStore::User);
+    Ok(())
+}
+
+/// Drop the cached session key, which no longer works, and read it from the
+/// browser again.
+#[tracing::instrument(name = "session", skip_all, fields(account = account))]
+pub fn refresh_cached_session(
+    account: Option<&str>,
+    selection: cookies::BrowserSelection,
+) -> Result<Session> {
+    tracing::info!("Cached session key was rejected, reading it from the browser again");
+    let mut config = read_config(config_file())?;
+    config.select_account(account, true)?;
+    if config.set_cached_key(None) {
+        config.write_store(KeyStore::User)?;
+    }
+    read_browser_session(selection)
+}
+
+/// Mask all but the first and last few characters of a session key
+pub fn redact(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+pub fn show_cmd(
+    client: &aoc::Client,
+    reveal: bool,
+    config: &Config,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let session = resolve_session_key(client, &None, config, selection)?;
+    let key = if reveal {
+        session.key.clone()
+    } else {
+        redact(&session.key)
+    };
+    say!("{key} (from {})", session.source);
+    report::set("key", &key);
+    report::set("source", session.source.to_string());
+    Ok(())
+}
+
+/// Show where the session key comes from and how old it is
+pub fn status_cmd(
+    client: &aoc::Client,
+    config: &Config,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let session = resolve_session_key(client, &None, config, selection)?;
+    say!("Session key from {}", session.source);
+    report::set("source", session.source.to_string());
+    report::set("created", session.created);
+    report::set("age_days", session.age_days());
+    match (session.created, session.age_days()) {
+        (Some(created), Some(days)) => say!(
+            "Obtained on {} ({days} days ago)",
+            created.format("%Y-%m-%d")
+        ),
+        _ => say!("Age unknown"),
+    }
+    session.warn_if_old();
+    Ok(())
+}
+
+pub fn test_cmd(
+    client: &aoc::Client,
+    config: &Config,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let session = resolve_session_key(client, &None, config, selection)?;
+    let user = aoc::check_session(client, &session.key)?;
+    report::set("source", session.source.to_string());
+    report::set("user", &user);
+    say!(
+        "{}",
+        color::say(
+            &format!(
+                "Session key from {} is logged in as {user}.",
+                session.source
+            ),
+            color::SUCCESS
+        )
+    );
+    Ok(())
+}
+
+/// Store the session key from a cookies.txt file if one is given, otherwise
+/// from the selected browser.
+pub fn import_cmd(
+    file: Option<&Path>,
+    local: bool,
+    account: Option<&str>,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let mut config = read_config(config_file())?;
+    config.select_account(account, true)?;
+    let (cookie, source) = match file {
+        Some(file) => cookies::read_cookie_file(file)?,
+        None => cookies::find_session_key(selection)?,
+    };
+    logging::register_secret(&cookie.value);
+    let store = if local {
+        KeyStore::Local
+    } else {
+        KeyStore::User
+    };
+    // the session is as old as the browser cookie, if the browser records that
+    let created = cookie.created.unwrap_or_else(Utc::now);
+    config.set_session_key(store, Some(cookie.value), Some(created));
+    config.write_store(store)?;
+    say!("Imported session key from {source} into {store}.");
+    report::set("source", source.to_string());
+    report::set("store", store.to_string());
+    Ok(())
+}
+
+pub fn clear_cmd(account: Option<&str>) -> Result<()> {
+    let mut config = read_config(config_file())?;
+    config.select_account(account, false)?;
+    let mut removed = Vec::new();
+    for store in [KeyStore::Local, KeyStore::User] {
+        if config.set_session_key(store, None, None) {
+            config.write_store(store)?;
+            say!("Removed session key from {store}.");
+            removed.push(store.to_string());
+        }
+    }
+    if config.set_cached_key(None) {
+        config.write_store(KeyStore::User)?;
+        say!(
+            "Removed cached browser session key from {}.",
+            KeyStore::User
+        );
+        removed.push(format!("cached key in {}", KeyStore::User));
+    }
+    report::set("removed", &removed);
+    if removed.is_empty() {
+        say!(
+            "No session key stored in {} or {}.",
+            KeyStore::Local,
+            KeyStore::User
+        );
+    }
+    Ok(())
+}
+
+/// Encrypt the session keys in the secrets file with a passphrase
+pub fn lock_cmd() -> Result<()> {
+    let mut config = read_config(config_file())?;
+    report::set("store", KeyStore::User.to_string());
+    report::set("changed", !config.secrets.locked);
+    if config.secrets.locked {
+        say!("{} is already locked.", KeyStore::User);
+        return Ok(());
+    }
+    vault::seal(&mut config.secrets, true)?;
+    config.secrets.locked = true;
+    config.write_store(KeyStore::User)?;
+    say!(
+        "Locked {}. You will be asked for the passphrase when the session key is used, \
+         unless it is set in ${}.",
+        KeyStore::User,
+        vault::PASSPHRASE_ENV_VAR
+    );
+    Ok(())
+}
+
+/// Decrypt the session keys in the secrets file
+pub fn unlock_cmd() -> Result<()> {
+    let mut config = read_config(config_file())?;
+    report::set("store", KeyStore::User.to_string());
+    report::set("changed", config.secrets.locked);
+    if !config.secrets.locked {
+        say!("{} is not locked.", KeyStore::User);
+        return Ok(());
+    }
+    vault::unseal(&mut config.secrets)?;
+    config.secrets.locked = false;
+    config.write_store(KeyStore::User)?;
+    say!("Unlocked {}.", KeyStore::User);
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! Interactive walk-through for setting up aochelper: the year, where inputs
+//! go, and the session key
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::config::{config_file, read_config, write_config, Config, KeyStore};
+use crate::prompt::{ask, confirm};
+use crate::report::say;
+use crate::{aoc, color, cookies, logging, report};
+
+const MANUAL_STEPS: &str = "\
+To copy your session cookie by hand:
+  1. Log into https://adventofcode.com in your browser.
+  2. Open the developer tools (F12, or Cmd+Option+I on macOS).
+  3. Firefox: go to Storage > Cookies > https://adventofcode.com.
+     Chrome, Edge and others: go to Application > Cookies > https://adventofcode.com.
+  4. Copy the value of the cookie named `session`, a long string of hex digits.";
+
+/// Check a session key, and ask whether to keep it if it can't be verified.
+fn verify(client: &aoc::Client, key: &str) -> Result<bool> {
+    match aoc::check_session(client, key) {
+        Ok(user) => {
+            say!(
+                "{}",
+                color::say(
+                    &format!("The session key is logged in as {user}."),
+                    color::SUCCESS
+                )
+            );
+            report::set("user", &user);
+            Ok(true)
+        }
+        Err(err) => {
+            say!("Could not verify the session key: {err}");
+            confirm("Store it anyway?", false)
+        }
+    }
+}
+
+/// Ask for the year of the puzzles, `default` if none is given
+fn ask_year(default: u16) -> Result<u16> {
+    loop {
+        let answer = ask(&format!(
+            "Which year's puzzles are you solving? [{default}]"
+        ))?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        let Ok(year) = answer.parse::<u16>() else {
+            say!("Please give a year, such as {default}.");
+            continue;
+        };
+        // A year that is about to start is fine to set up ahead of time
+        match aoc::check_puzzle(year, 1) {
+            Err(err) if !err.is::<aoc::NotUnlocked>() => say!("{err}"),
+            _ => return Ok(year),
+        }
+    }
+}
+
+/// Ask for the year when it isn't given or configured, and offer to store it
+/// in aochelper.toml
+pub fn ask_missing_year() -> Result<u16> {
+    say!(
+        "No year was given with --year or set in {}.",
+        config_file().display()
+    );
+    let year = ask_year(aoc::max_year())?;
+    if confirm(
+        &format!("Save {year} as the year in {}?", config_file().display()),
+        true,
+    )? {
+        let mut config = read_config(config_file())?;
+        config.year = Some(year);
+        write_config(&config)?;
+        say!("Saved the year in {}.", config_file().display());
+    }
+    Ok(year)
+}
+
+/// Ask for the directory to save inputs in, `default` if none is given
+fn ask_output(default: &Path) -> Result<PathBuf> {
+    let answer = ask(&format!(
+        "Which directory should inputs be saved in? [{}]",
+        default.display()
+    ))?;
+    Ok(match answer.as_str() {
+        "" => default.to_path_buf(),
+        dir => PathBuf::from(dir),
+    })
+}
+
+/// Ask for the year and the directory for inputs and store them in
+/// aochelper.toml, then set up the session key unless one is stored and the
+/// user keeps it.
+pub fn setup_cmd(
+    client: &aoc::Client,
+    local: bool,
+    account: Option<&str>,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let mut config = read_config(config_file())?;
+    config.select_account(account, true)?;
+
+    let year = ask_year(config.year.unwrap_or_else(aoc::max_year))?;
+    let default_output = config
+        .output_path()
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("inputs"));
+    let output = ask_output(&default_output)?;
+    config.year = Some(year);
+    *config.output_path_mut() = Some(output.clone());
+    write_config(&config)?;
+    say!(
+        "Saved the year and the directory for inputs in {}.",
+        config_file().display()
+    );
+    report::set("year", year);
+    report::set("output_path", &output);
+
+    if let Some(stored) = config.session_key() {
+        let question = format!(
+            "A session key is already stored in {}. Set up a new one?",
+            stored.store
+        );
+        if !confirm(&question, false)? {
+            say!("{}", color::say("You're all set!", color::SUCCESS));
+            return Ok(());
+        }
+    }
+    setup_session(client, &mut config, local, selection)
+}
+
+/// Ask the user to paste a session key until they give one that works, or
+/// that they want to keep anyway
+fn ask_session_key(client: &aoc::Client) -> Result<String> {
+    say!("\n{MANUAL_STEPS}\n");
+    loop {
+        let key = ask("Paste your session key:")?;
+        let key = key.trim_start_matches("session=").to_string();
+        if key.is_empty() {
+            say!("The session key can't be empty.");
+            continue;
+        }
+        logging::register_secret(&key);
+        if verify(client, &key)? {
+            return Ok(key);
+        }
+    }
+}
+
+/// Ask for a session key when none could be found, and offer to store it in
+/// the user's secrets file
+pub fn ask_missing_session_key(client: &aoc::Client, account: Option<&str>) -> Result<String> {
+    say!("No session key was found, in the configuration or in your browsers.");
+    let key = ask_session_key(client)?;
+    if confirm(&format!("Store it in {}?", KeyStore::User), true)? {
+        let mut config = read_config(config_file())?;
+        config.select_account(account, true)?;
+        config.set_session_key(KeyStore::User, Some(key.clone()), Some(Utc::now()));
+        config.write_store(KeyStore::User)?;
+        say!("Stored the session key in {}.", KeyStore::User);
+    }
+    Ok(key)
+}
+
+/// Find a session key in the browser, or else ask the user to paste one, check
+/// it and store it.
+fn setup_session(
+    client: &aoc::Client,
+    config: &mut Config,
+    local: bool,
+    selection: cookies::BrowserSelection,
+) -> Result<()> {
+    let mut found = None;
+    if !selection.disabled {
+        say!("Looking for your Advent of Code session in your browsers...");
+        match cookies::find_session_key(selection) {
+            Ok((cookie, browser)) => {
+                logging::register_secret(&cookie.value);
+                say!("Found a session cookie in {browser}.");
+                if verify(client, &cookie.value)? {
+                    found = Some((cookie.value, cookie.created));
+                }
+            }
+            Err(err) => {
+                tracing::debug!("{err:#}");
+                say!("No usable session cookie found in your browsers.");
+            }
+        }
+    }
+
+    let (key, created) = match found {
+        Some(found) => found,
+        None => (ask_session_key(client)?, None),
+    };
+
+    let store = if local {
+        KeyStore::Local
+    } else {
+        KeyStore::User
+    };
+    config.set_session_key(store, Some(key), Some(created.unwrap_or_else(Utc::now)));
+    config.write_store(store)?;
+    say!(
+        "{}",
+        color::say(
+            &format!("Stored the session key in {store}. You're all set!"),
+            color::SUCCESS
+        )
+    );
+    report::set("store", store.to_string());
+    Ok(())
+}
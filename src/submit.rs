@@ -0,0 +1,417 @@
+//! Verdicts on submitted answers, and the cooldowns after wrong ones
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::color;
+use crate::config::data_dir;
+
+/// What Advent of Code made of a submitted answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Correct,
+    Incorrect,
+    TooHigh,
+    TooLow,
+    /// An answer was given too recently; `wait` is the time left, if stated
+    RateLimited {
+        wait: Option<Duration>,
+    },
+    /// The part was already solved
+    AlreadySolved,
+    /// The part can't be answered, e.g. part 2 before part 1 is solved
+    WrongLevel,
+    /// A message that isn't recognized
+    Unknown,
+}
+
+impl Verdict {
+    /// Classify the message returned for an answer to `part`
+    pub fn parse(message: &str, part: u8) -> Verdict {
+        if message.contains("That's the right answer") {
+            Verdict::Correct
+        } else if message.contains("answer is too high") {
+            Verdict::TooHigh
+        } else if message.contains("answer is too low") {
+            Verdict::TooLow
+        } else if message.contains("That's not the right answer") {
+            Verdict::Incorrect
+        } else if message.contains("You gave an answer too recently") {
+            Verdict::RateLimited {
+                wait: parse_wait(message),
+            }
+        } else if message.contains("You don't seem to be solving the right level") {
+            // part 1 is always open, so it must have been solved already
+            if part == 1 {
+                Verdict::AlreadySolved
+            } else {
+                Verdict::WrongLevel
+            }
+        } else {
+            Verdict::Unknown
+        }
+    }
+
+    /// Name the verdict is stored under in the answer history
+    pub fn id(&self) -> &'static str {
+        match self {
+            Verdict::Correct => "correct",
+            Verdict::Incorrect => "incorrect",
+            Verdict::TooHigh => "too_high",
+            Verdict::TooLow => "too_low",
+            Verdict::RateLimited { .. } => "rate_limited",
+            Verdict::AlreadySolved => "already_solved",
+            Verdict::WrongLevel => "wrong_level",
+            Verdict::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Verdict {
+        match id {
+            "correct" => Verdict::Correct,
+            "incorrect" => Verdict::Incorrect,
+            "too_high" => Verdict::TooHigh,
+            "too_low" => Verdict::TooLow,
+            "rate_limited" => Verdict::RateLimited { wait: None },
+            "already_solved" => Verdict::AlreadySolved,
+            "wrong_level" => Verdict::WrongLevel,
+            _ => Verdict::Unknown,
+        }
+    }
+
+    /// The verdict, in color when printed to a terminal
+    pub fn colored(&self) -> String {
+        let style = match self {
+            Verdict::Correct => color::SUCCESS,
+            Verdict::Incorrect | Verdict::TooHigh | Verdict::TooLow => color::FAILURE,
+            _ => color::WARNING,
+        };
+        color::say(&self.to_string(), style)
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Correct => write!(f, "Correct!"),
+            Verdict::Incorrect => write!(f, "Wrong answer."),
+            Verdict::TooHigh => write!(f, "Wrong answer: too high."),
+            Verdict::TooLow => write!(f, "Wrong answer: too low."),
+            Verdict::RateLimited { wait: Some(wait) } => write!(
+                f,
+                "Answered too recently; wait {} before trying again.",
+                format_duration(*wait)
+            ),
+            Verdict::RateLimited { wait: None } => {
+                write!(f, "Answered too recently; wait before trying again.")
+            }
+            Verdict::AlreadySolved => write!(f, "Already solved."),
+            Verdict::WrongLevel => write!(
+                f,
+                "Wrong level: this part is either not unlocked yet or already solved."
+            ),
+            Verdict::Unknown => write!(f, "Unrecognized response; rerun with --raw to see it."),
+        }
+    }
+}
+
+/// How long Advent of Code makes you wait before the next answer, going by
+/// "Please wait one minute before trying again." or "...please wait 5 minutes
+/// before trying again." after a wrong answer, or "You have 4m 32s left to
+/// wait." after answering too soon
+pub fn parse_cooldown(message: &str) -> Option<Duration> {
+    if let Some(wait) = parse_wait(message) {
+        return Some(wait);
+    }
+    let message = message.to_lowercase();
+    let (rest, _) = message.split_once(" before trying again")?;
+    let (_, wait) = rest.rsplit_once("wait ")?;
+    let (number, unit) = wait.split_once(' ')?;
+    let number = match number {
+        "one" => 1,
+        number => number.parse().ok()?,
+    };
+    match unit {
+        "minute" | "minutes" => Some(Duration::from_secs(number * 60)),
+        "hour" | "hours" => Some(Duration::from_secs(number * 3600)),
+        _ => None,
+    }
+}
+
+/// Parse "You have 4m 32s left to wait."
+fn parse_wait(message: &str) -> Option<Duration> {
+    let (_, rest) = message.split_once("You have ")?;
+    let (wait, _) = rest.split_once(" left to wait")?;
+    let mut seconds = 0;
+    for part in wait.split_whitespace() {
+        let (number, unit) = part.split_at(part.find(|c: char| !c.is_ascii_digit())?);
+        let number: u64 = number.parse().ok()?;
+        seconds += match unit {
+            "h" => number * 3600,
+            "m" => number * 60,
+            "s" => number,
+            _ => return None,
+        };
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (d, h, m, s) = (
+        seconds / 86400,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+    );
+    [(d, "d"), (h, "h"), (m, "m"), (s, "s")]
+        .iter()
+        .filter(|(n, _)| *n != 0)
+        .map(|(n, unit)| format!("{n}{unit}"))
+        .reduce(|a, b| format!("{a} {b}"))
+        .unwrap_or_else(|| "0s".to_string())
+}
+
+/// Error returned when a part was answered too recently to answer it again
+#[derive(Debug)]
+pub struct AnswerCooldown {
+    pub day: u8,
+    pub part: u8,
+    pub wait: Duration,
+}
+
+impl fmt::Display for AnswerCooldown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "You have to wait {} before answering day {} part {} again. \
+             Pass --wait to wait and then submit.",
+            format_duration(self.wait),
+            self.day,
+            self.part
+        )
+    }
+}
+
+impl std::error::Error for AnswerCooldown {}
+
+const COOLDOWN_FILE: &str = "cooldowns.toml";
+/// Held while updating the cooldowns file, by other runs as well
+const COOLDOWN_LOCK_FILE: &str = "cooldowns.lock";
+
+/// Serializes updates of the cooldowns file between threads, such as
+/// concurrent downloads; the lock file serializes them between runs
+static COOLDOWNS_LOCK: Mutex<()> = Mutex::new(());
+
+/// When each puzzle part can be answered again, by [`Cooldowns::key`], and
+/// until when adventofcode.com asked us to back off from requests
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Cooldowns(BTreeMap<String, DateTime<Utc>>);
+
+impl Cooldowns {
+    fn path() -> Option<PathBuf> {
+        Some(data_dir()?.join(COOLDOWN_FILE))
+    }
+
+    pub fn key(account: Option<&str>, year: u16, day: u8, part: u8) -> String {
+        format!("{}/{year}/{day}/{part}", account.unwrap_or("default"))
+    }
+
+    pub fn load() -> Result<Cooldowns> {
+        match Cooldowns::path() {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {:?}", &path))?;
+                toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", &path))
+            }
+            _ => Ok(Cooldowns::default()),
+        }
+    }
+
+    /// Write the cooldowns next to `path` and rename them over it, so that
+    /// a run reading the file never sees it half written
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        fs::write(&partial, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {:?}", &partial))?;
+        fs::rename(&partial, path).with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Read the cooldowns file, change it with `update` and write it back,
+    /// dropping expired cooldowns, all while holding the lock on it. Anything
+    /// recorded meanwhile, by another thread or run, is kept that way.
+    pub fn update<T>(update: impl FnOnce(&mut Cooldowns) -> T) -> Result<T> {
+        let _lock = COOLDOWNS_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(path) = Cooldowns::path() else {
+            return Ok(update(&mut Cooldowns::default()));
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)?;
+        let lock_path = dir.join(COOLDOWN_LOCK_FILE);
+        let lock_file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open {:?}", &lock_path))?;
+        let mut file_lock = fd_lock::RwLock::new(lock_file);
+        let _file_lock = file_lock
+            .write()
+            .with_context(|| format!("Failed to lock {:?}", &lock_path))?;
+
+        let mut cooldowns = Cooldowns::load()?;
+        let result = update(&mut cooldowns);
+        let now = Utc::now();
+        cooldowns.0.retain(|_, deadline| *deadline > now);
+        cooldowns.save(&path)?;
+        Ok(result)
+    }
+
+    /// Time left before `key` can be answered again, if any
+    pub fn remaining(&self, key: &str) -> Option<Duration> {
+        let deadline = self.0.get(key)?;
+        (*deadline - Utc::now()).to_std().ok()
+    }
+
+    /// Set the cooldown of `key` to end after `cooldown`, or remove it
+    pub fn set(&mut self, key: String, cooldown: Option<Duration>) {
+        match cooldown.and_then(|cooldown| chrono::Duration::from_std(cooldown).ok()) {
+            Some(cooldown) => {
+                self.0.insert(key, Utc::now() + cooldown);
+            }
+            None => {
+                self.0.remove(&key);
+            }
+        }
+    }
+
+    /// Record the cooldown after an answer to `key`, or a back-off from
+    /// requests
+    pub fn record(key: String, cooldown: Option<Duration>) -> Result<()> {
+        Cooldowns::update(|cooldowns| cooldowns.set(key, cooldown))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cooldown_of_one_minute() {
+        let message = "That's not the right answer. If you're stuck, make sure you're using \
+                       the full input data. Please wait one minute before trying again.";
+        assert_eq!(parse_cooldown(message), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn cooldown_of_several_minutes() {
+        let message = "That's not the right answer; your answer is too low. Because you have \
+                       guessed incorrectly 4 times on this puzzle, please wait 5 minutes \
+                       before trying again.";
+        assert_eq!(parse_cooldown(message), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn cooldown_left_to_wait() {
+        let message = "You gave an answer too recently; you have to wait after submitting an \
+                       answer before trying again. You have 4m 32s left to wait.";
+        assert_eq!(parse_cooldown(message), Some(Duration::from_secs(272)));
+    }
+
+    #[test]
+    fn no_cooldown() {
+        assert_eq!(parse_cooldown("That's the right answer!"), None);
+    }
+
+    #[test]
+    fn cooldowns_set_and_removed() {
+        let mut cooldowns = Cooldowns::default();
+        cooldowns.set(
+            "default/2022/1/1".to_string(),
+            Some(Duration::from_secs(60)),
+        );
+        let remaining = cooldowns.remaining("default/2022/1/1").unwrap();
+        assert!(remaining > Duration::from_secs(58) && remaining <= Duration::from_secs(60));
+        assert_eq!(cooldowns.remaining("default/2022/1/2"), None);
+        cooldowns.set("default/2022/1/1".to_string(), None);
+        assert_eq!(cooldowns.remaining("default/2022/1/1"), None);
+    }
+
+    #[test]
+    fn verdicts() {
+        let parse = |message| Verdict::parse(message, 1);
+        assert_eq!(
+            parse("That's the right answer! You are one gold star closer."),
+            Verdict::Correct
+        );
+        assert_eq!(
+            parse("That's not the right answer; your answer is too high."),
+            Verdict::TooHigh
+        );
+        assert_eq!(
+            parse("That's not the right answer; your answer is too low."),
+            Verdict::TooLow
+        );
+        assert_eq!(
+            parse("That's not the right answer. If you're stuck, make sure you're using the full input data."),
+            Verdict::Incorrect
+        );
+        assert_eq!(parse("Something else entirely."), Verdict::Unknown);
+    }
+
+    #[test]
+    fn rate_limited_verdict() {
+        let message = "You gave an answer too recently; you have to wait after submitting an \
+                       answer before trying again. You have 32s left to wait.";
+        assert_eq!(
+            Verdict::parse(message, 1),
+            Verdict::RateLimited {
+                wait: Some(Duration::from_secs(32))
+            }
+        );
+        let message = "You gave an answer too recently.";
+        assert_eq!(
+            Verdict::parse(message, 1),
+            Verdict::RateLimited { wait: None }
+        );
+    }
+
+    #[test]
+    fn wrong_level_verdict() {
+        let message = "You don't seem to be solving the right level.  Did you already complete it?";
+        assert_eq!(Verdict::parse(message, 1), Verdict::AlreadySolved);
+        assert_eq!(Verdict::parse(message, 2), Verdict::WrongLevel);
+    }
+
+    #[test]
+    fn verdict_ids_round_trip() {
+        for verdict in [
+            Verdict::Correct,
+            Verdict::Incorrect,
+            Verdict::TooHigh,
+            Verdict::TooLow,
+            Verdict::RateLimited { wait: None },
+            Verdict::AlreadySolved,
+            Verdict::WrongLevel,
+            Verdict::Unknown,
+        ] {
+            assert_eq!(Verdict::from_id(verdict.id()), verdict);
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_secs(272)), "4m 32s");
+        assert_eq!(format_duration(Duration::from_secs(90061)), "1d 1h 1m 1s");
+    }
+}
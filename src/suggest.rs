@@ -0,0 +1,23 @@
+//! Suggestions for misspelled names, in the way clap suggests subcommands
+
+/// How alike two names must be for one to be suggested for the other. A bit
+/// stricter than clap, as account names can be as short as a letter.
+const MIN_SIMILARITY: f64 = 0.8;
+
+/// The candidate most like `name`, if any is alike enough
+pub fn closest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|&candidate| (strsim::jaro(name, candidate), candidate))
+        .filter(|&(similarity, _)| similarity > MIN_SIMILARITY)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+/// " Did you mean 'x'?" for the candidate most like `name`, or nothing
+pub fn did_you_mean(name: &str, candidates: &[&str]) -> String {
+    match closest(name, candidates) {
+        Some(candidate) => format!(" Did you mean '{candidate}'?"),
+        None => String::new(),
+    }
+}
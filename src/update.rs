@@ -0,0 +1,355 @@
+//! Updating aochelper to the latest release on GitHub, for those who didn't
+//! install it with cargo. Releases have a binary for each platform, named
+//! `aochelper-<target>`, and its SHA-256 checksum in `<binary>.sha256`.
+//!
+//! Once a day, commands run on a terminal also look for a new release while
+//! they work, and mention it when they are done.
+
+use std::env;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{config_file, data_dir, read_config};
+use crate::report::{self, say};
+use crate::{aoc, color, dry_run};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/blairfrandeen/aochelper/releases/latest";
+
+/// Binaries take longer to download than pages
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Result<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Release {} has no {name}. Download aochelper from {}/releases, or install it with `cargo install aochelper`.",
+                    self.tag_name,
+                    env!("CARGO_PKG_REPOSITORY")
+                )
+            })
+    }
+}
+
+/// Name of the release binary for the platform aochelper runs on
+fn binary_name() -> Result<String> {
+    let target = match (env::consts::ARCH, env::consts::OS) {
+        ("x86_64", "linux") => "x86_64-unknown-linux-gnu",
+        ("aarch64", "linux") => "aarch64-unknown-linux-gnu",
+        ("x86_64", "macos") => "x86_64-apple-darwin",
+        ("aarch64", "macos") => "aarch64-apple-darwin",
+        ("x86_64", "windows") => "x86_64-pc-windows-msvc",
+        (arch, os) => {
+            return Err(anyhow::anyhow!(
+                "There are no release binaries for {arch} {os}. Install aochelper with `cargo install aochelper` instead."
+            ))
+        }
+    };
+    Ok(format!("aochelper-{target}{}", env::consts::EXE_SUFFIX))
+}
+
+/// Numbers of a version such as v1.2.3, without any pre-release part
+fn version_numbers(version: &str) -> Vec<u64> {
+    let version = version.trim_start_matches('v');
+    let version = version.split(['-', '+']).next().unwrap_or(version);
+    version
+        .split('.')
+        .map(|number| number.parse().unwrap_or(0))
+        .collect()
+}
+
+/// The latest release, as GitHub describes it
+fn latest_release(
+    client: &reqwest::blocking::Client,
+    timeout: Option<Duration>,
+) -> Result<Release> {
+    tracing::debug!("Checking {LATEST_RELEASE_URL}");
+    let mut request = client
+        .get(LATEST_RELEASE_URL)
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    let res = request
+        .send()?
+        .error_for_status()
+        .context("Failed to look up the latest release")?;
+    serde_json::from_slice(&res.bytes()?)
+        .context("Unexpected answer from GitHub about the latest release")
+}
+
+/// Whether `latest` is a newer version than this one
+fn is_newer(latest: &str) -> bool {
+    version_numbers(latest) > version_numbers(env!("CARGO_PKG_VERSION"))
+}
+
+fn download(client: &reqwest::blocking::Client, url: &str) -> Result<Vec<u8>> {
+    tracing::debug!("Downloading {url}");
+    let res = client
+        .get(url)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()?
+        .error_for_status()?;
+    Ok(res.bytes()?.to_vec())
+}
+
+/// Check `binary` against the checksum file of the release, which holds the
+/// hex digest, optionally followed by the file name as sha256sum writes it
+fn verify(binary: &[u8], checksum: &[u8]) -> Result<()> {
+    let checksum = String::from_utf8_lossy(checksum);
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("The checksum file of the release is empty."))?;
+    let actual: String = Sha256::digest(binary)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(anyhow::anyhow!(
+            "The downloaded binary doesn't match its checksum (expected {expected}, got {actual}). Nothing was changed."
+        ));
+    }
+    Ok(())
+}
+
+/// Where the running executable is moved on Windows, which doesn't allow
+/// replacing it, but does allow renaming it
+fn old_exe_path(exe: &Path) -> PathBuf {
+    exe.with_extension("old.exe")
+}
+
+/// Put `binary` in place of the running executable
+fn replace_exe(exe: &Path, binary: &[u8]) -> Result<()> {
+    let dir = exe
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Can't tell the directory of {:?}", exe))?;
+    // In the same directory, so that renaming it over the executable can't
+    // fail halfway
+    let mut new = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Can't write to {:?}. Try again with permission to.", dir))?;
+    new.write_all(binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(new.path(), fs::Permissions::from_mode(0o755))?;
+    }
+    let old = old_exe_path(exe);
+    if cfg!(windows) {
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).with_context(|| format!("Failed to move {:?} aside", exe))?;
+    }
+    if let Err(err) = new.persist(exe) {
+        if cfg!(windows) {
+            let _ = fs::rename(&old, exe);
+        }
+        return Err(err.error).with_context(|| format!("Failed to replace {:?}", exe));
+    }
+    Ok(())
+}
+
+/// Update aochelper to the latest release, or with `check`, only tell whether
+/// there is a newer one
+pub fn self_update_cmd(client: &aoc::Client, check: bool) -> Result<()> {
+    let exe = env::current_exe().context("Can't tell where aochelper is installed")?;
+    if dry_run::enabled() {
+        let action = if check {
+            "tell whether it is newer".to_string()
+        } else {
+            format!("replace {} with it if it is newer", exe.display())
+        };
+        dry_run::would(format_args!(
+            "look up the latest release at {LATEST_RELEASE_URL} and {action}"
+        ));
+        report::set("updated", false);
+        return Ok(());
+    }
+    if cfg!(windows) {
+        // Left behind by the last update
+        let _ = fs::remove_file(old_exe_path(&exe));
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    let client = client.http()?;
+    let release = latest_release(&client, None)?;
+    let latest = release.tag_name.trim_start_matches('v');
+    report::set("current", current);
+    report::set("latest", latest);
+    if !is_newer(latest) {
+        say!("aochelper {current} is the latest version.");
+        report::set("updated", false);
+        return Ok(());
+    }
+    if check {
+        say!(
+            "aochelper {latest} is out; this is {current}. Run `aochelper self-update` to update."
+        );
+        report::set("updated", false);
+        return Ok(());
+    }
+
+    let name = binary_name()?;
+    let binary_asset = release.asset(&name)?;
+    let checksum_asset = release.asset(&format!("{name}.sha256"))?;
+    say!("Downloading aochelper {latest}...");
+    let binary = download(&client, &binary_asset.browser_download_url)?;
+    let checksum = download(&client, &checksum_asset.browser_download_url)?;
+    verify(&binary, &checksum)?;
+    replace_exe(&exe, &binary)?;
+
+    say!(
+        "{}",
+        color::say(
+            &format!("Updated aochelper from {current} to {latest}."),
+            color::SUCCESS
+        )
+    );
+    report::set("updated", true);
+    report::set("path", &exe);
+    Ok(())
+}
+
+const LAST_CHECK_FILE: &str = "update_check.toml";
+
+/// The background check gives up after this long
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a command waits for a background check that isn't done yet
+const CHECK_WAIT: Duration = Duration::from_millis(500);
+
+/// When the latest release was last looked up, and what it was
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct LastCheck {
+    checked: Option<DateTime<Utc>>,
+    latest: Option<String>,
+}
+
+impl LastCheck {
+    fn path() -> Option<PathBuf> {
+        Some(data_dir()?.join(LAST_CHECK_FILE))
+    }
+
+    fn load() -> Result<LastCheck> {
+        match LastCheck::path() {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {:?}", &path))?;
+                toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", &path))
+            }
+            _ => Ok(LastCheck::default()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = LastCheck::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {:?}", &path))
+    }
+}
+
+/// Latest release known from the last check, and the check running now, if
+/// it is time for one
+struct UpdateCheck {
+    known: Option<String>,
+    running: Option<mpsc::Receiver<String>>,
+}
+
+static CHECK: Mutex<Option<UpdateCheck>> = Mutex::new(None);
+
+/// Look for a new release in the background, unless that was done in the last
+/// day, the output isn't a terminal, or `update_check = false` is set
+pub fn start_check(client: &aoc::Client) {
+    // Recording the check would write a file
+    if !std::io::stderr().is_terminal() || dry_run::enabled() {
+        return;
+    }
+    let Ok(config) = read_config(config_file()) else {
+        return;
+    };
+    if config.update_check == Some(false) {
+        return;
+    }
+    let Ok(client) = client.http() else {
+        return;
+    };
+    let last = LastCheck::load().unwrap_or_default();
+    let due = last.checked.map_or(true, |checked| {
+        Utc::now() - checked >= chrono::Duration::days(1)
+    });
+    let running = due.then(|| {
+        let (sender, receiver) = mpsc::channel();
+        let known = last.latest.clone();
+        thread::spawn(move || {
+            let latest = match latest_release(&client, Some(CHECK_TIMEOUT)) {
+                Ok(release) => Some(release.tag_name.trim_start_matches('v').to_string()),
+                Err(err) => {
+                    tracing::debug!("Failed to check for a new release: {err:#}");
+                    known
+                }
+            };
+            // Even after a failure, so that being offline doesn't mean
+            // trying again on every run
+            let checked = LastCheck {
+                checked: Some(Utc::now()),
+                latest: latest.clone(),
+            };
+            if let Err(err) = checked.save() {
+                tracing::debug!("Failed to record the check for a new release: {err:#}");
+            }
+            if let Some(latest) = latest {
+                let _ = sender.send(latest);
+            }
+        });
+        receiver
+    });
+    *CHECK.lock().unwrap_or_else(|err| err.into_inner()) = Some(UpdateCheck {
+        known: last.latest,
+        running,
+    });
+}
+
+/// Mention a newer release, if the check found one
+pub fn notify() {
+    let Some(check) = CHECK.lock().unwrap_or_else(|err| err.into_inner()).take() else {
+        return;
+    };
+    let latest = check
+        .running
+        .and_then(|running| running.recv_timeout(CHECK_WAIT).ok())
+        .or(check.known);
+    if let Some(latest) = latest.filter(|latest| is_newer(latest)) {
+        eprintln!(
+            "{} aochelper {latest} is available (this is {}). Update with `aochelper self-update` or `cargo install aochelper`.",
+            color::err("Note:", color::WARNING),
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+}
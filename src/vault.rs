@@ -0,0 +1,274 @@
+//! Passphrase encryption of the session keys in the secrets file, for machines
+//! without a keyring, and of archives made with `archive --encrypt`. Each key
+//! or archive is encrypted with AES-256-GCM, using a key derived from the
+//! passphrase with PBKDF2-HMAC-SHA256 and a random salt.
+
+use std::env;
+use std::sync::Mutex;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, OsRng};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use anyhow::{Context, Result};
+use base64::Engine;
+use sha2::Sha256;
+
+use crate::config::Secrets;
+use crate::prompt;
+
+/// Prefix marking an encrypted session key in the secrets file
+const PREFIX: &str = "encrypted:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+#[cfg(not(test))]
+const ITERATIONS: u32 = 600_000;
+/// Few enough for tests to run quickly in a debug build
+#[cfg(test)]
+const ITERATIONS: u32 = 1_000;
+
+/// Environment variable the passphrase is read from, if set, instead of
+/// asking on the terminal
+pub const PASSPHRASE_ENV_VAR: &str = "AOCHELPER_PASSPHRASE";
+
+/// Environment variable the passphrase of archives is read from, if set,
+/// instead of asking on the terminal
+pub const ARCHIVE_PASSPHRASE_ENV_VAR: &str = "AOCHELPER_ARCHIVE_PASSPHRASE";
+
+/// Start of an encrypted archive, followed by the salt, nonce and ciphertext
+const ARCHIVE_MAGIC: &[u8] = b"aochelper-encrypted-1\n";
+
+/// Passphrase entered earlier, so it is only asked for once per run
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, ITERATIONS, &mut key);
+    key
+}
+
+/// Encrypt `data` into the salt, the nonce and the ciphertext
+fn seal_bytes(data: &[u8], passphrase: &str, what: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(&derive_key(passphrase, &salt))
+        .encrypt(&nonce, data)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt {what}"))?;
+    Ok([&salt[..], &nonce[..], &ciphertext[..]].concat())
+}
+
+/// Decrypt what [`seal_bytes`] returned
+fn open_bytes(blob: &[u8], passphrase: &str, what: &str) -> Result<Vec<u8>> {
+    if blob.len() < SALT_LEN + NONCE_LEN {
+        return Err(anyhow::anyhow!("Encrypted {what} is too short"));
+    }
+    let (salt, rest) = blob.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    Aes256Gcm::new(&derive_key(passphrase, salt))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase, or the encrypted {what} is damaged."))
+}
+
+fn encrypt(value: &str, passphrase: &str) -> Result<String> {
+    let blob = seal_bytes(value.as_bytes(), passphrase, "the session key")?;
+    Ok(format!(
+        "{PREFIX}{}",
+        base64::engine::general_purpose::STANDARD.encode(blob)
+    ))
+}
+
+fn decrypt(value: &str, passphrase: &str) -> Result<String> {
+    let blob = base64::engine::general_purpose::STANDARD
+        .decode(value.trim_start_matches(PREFIX))
+        .context("Encrypted session key is not valid base64")?;
+    Ok(String::from_utf8(open_bytes(
+        &blob,
+        passphrase,
+        "session key",
+    )?)?)
+}
+
+/// Get a passphrase from `env_var`, or else by asking for the one of `what`.
+/// With `confirm`, a new passphrase is asked for twice.
+fn read_passphrase(what: &str, env_var: &str, confirm: bool) -> Result<String> {
+    let passphrase = match env::var(env_var) {
+        Ok(passphrase) => passphrase,
+        Err(_) if !prompt::allowed() && confirm => {
+            return Err(anyhow::anyhow!(
+                "With --yes, give the passphrase to encrypt {what} with in ${env_var}."
+            ))
+        }
+        Err(_) if !prompt::allowed() => {
+            return Err(anyhow::anyhow!(
+                "{} is encrypted. With --yes, give its passphrase in ${env_var}.",
+                capitalize(what)
+            ))
+        }
+        Err(_) => {
+            let passphrase = rpassword::prompt_password(format!("Passphrase for {what}: "))?;
+            if confirm && rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+                return Err(anyhow::anyhow!("Passphrases don't match."));
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("The passphrase can't be empty."));
+    }
+    Ok(passphrase)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Get the passphrase from $AOCHELPER_PASSPHRASE, from earlier in this run, or
+/// else by asking for it. With `confirm`, a new passphrase is asked for twice.
+fn passphrase(confirm: bool) -> Result<String> {
+    let mut cached = PASSPHRASE.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = read_passphrase("the session key", PASSPHRASE_ENV_VAR, confirm)?;
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+pub fn is_encrypted_archive(data: &[u8]) -> bool {
+    data.starts_with(ARCHIVE_MAGIC)
+}
+
+/// Encrypt an archive with a new passphrase, from
+/// $AOCHELPER_ARCHIVE_PASSPHRASE or asked for twice
+pub fn encrypt_archive(data: &[u8]) -> Result<Vec<u8>> {
+    let passphrase = read_passphrase("the archive", ARCHIVE_PASSPHRASE_ENV_VAR, true)?;
+    let blob = seal_bytes(data, &passphrase, "the archive")?;
+    Ok([ARCHIVE_MAGIC, &blob].concat())
+}
+
+/// Decrypt an archive [`encrypt_archive`] made, asking for its passphrase
+pub fn decrypt_archive(data: &[u8]) -> Result<Vec<u8>> {
+    let passphrase = read_passphrase("the archive", ARCHIVE_PASSPHRASE_ENV_VAR, false)?;
+    open_bytes(&data[ARCHIVE_MAGIC.len()..], &passphrase, "archive")
+}
+
+/// Decrypt a session key read from the secrets file, if it is encrypted
+pub fn reveal(value: &str) -> Result<String> {
+    if is_encrypted(value) {
+        decrypt(value, &passphrase(false)?)
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Encrypt all session keys in `secrets` that aren't encrypted yet. If some
+/// already are, the passphrase is checked against them first so that all keys
+/// end up with the same passphrase.
+pub fn seal(secrets: &mut Secrets, confirm: bool) -> Result<()> {
+    let passphrase = passphrase(confirm)?;
+    let mut keys = secrets.keys_mut();
+    if let Some(encrypted) = keys.iter().find(|key| is_encrypted(key)) {
+        decrypt(encrypted, &passphrase)?;
+    }
+    for key in keys.iter_mut() {
+        if !is_encrypted(key) {
+            **key = encrypt(key, &passphrase)?;
+        }
+    }
+    Ok(())
+}
+
+/// Decrypt all encrypted session keys in `secrets`
+pub fn unseal(secrets: &mut Secrets) -> Result<()> {
+    for key in secrets.keys_mut() {
+        if is_encrypted(key) {
+            *key = decrypt(key, &passphrase(false)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_bytes_open() {
+        let blob = seal_bytes(b"some input", "passphrase", "the input").unwrap();
+        assert_ne!(&blob[SALT_LEN + NONCE_LEN..], b"some input");
+        let data = open_bytes(&blob, "passphrase", "input").unwrap();
+        assert_eq!(data, b"some input");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let blob = seal_bytes(b"some input", "passphrase", "the input").unwrap();
+        let err = open_bytes(&blob, "other passphrase", "input").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Wrong passphrase, or the encrypted input is damaged."
+        );
+    }
+
+    #[test]
+    fn damaged_blob() {
+        let mut blob = seal_bytes(b"some input", "passphrase", "the input").unwrap();
+        *blob.last_mut().unwrap() ^= 1;
+        assert!(open_bytes(&blob, "passphrase", "input").is_err());
+    }
+
+    #[test]
+    fn too_short_blob() {
+        let err = open_bytes(&[0; SALT_LEN + NONCE_LEN - 1], "passphrase", "input").unwrap_err();
+        assert_eq!(err.to_string(), "Encrypted input is too short");
+    }
+
+    #[test]
+    fn session_keys_round_trip() {
+        let encrypted = encrypt("53616c7465645f5f", "passphrase").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(
+            decrypt(&encrypted, "passphrase").unwrap(),
+            "53616c7465645f5f"
+        );
+    }
+
+    #[test]
+    fn seal_keeps_to_the_passphrase_of_encrypted_keys() {
+        let other = encrypt("first", "other passphrase").unwrap();
+        let mut secrets = Secrets {
+            session_key: Some(other.clone()),
+            ..Secrets::default()
+        };
+        secrets.accounts.insert(
+            "work".to_string(),
+            crate::config::AccountSecrets {
+                session_key: Some("second".to_string()),
+                ..Default::default()
+            },
+        );
+        // The only test using the passphrase of the run
+        *PASSPHRASE.lock().unwrap() = Some("passphrase".to_string());
+
+        assert!(seal(&mut secrets, false).is_err());
+        assert_eq!(secrets.session_key.as_deref(), Some(other.as_str()));
+        assert_eq!(
+            secrets.accounts["work"].session_key.as_deref(),
+            Some("second")
+        );
+
+        secrets.session_key = Some(encrypt("first", "passphrase").unwrap());
+        seal(&mut secrets, false).unwrap();
+        let work = secrets.accounts["work"].session_key.as_deref().unwrap();
+        assert!(is_encrypted(work));
+        assert_eq!(decrypt(work, "passphrase").unwrap(), "second");
+    }
+}